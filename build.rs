@@ -7,12 +7,78 @@
 //! Cargo re-run the build script whenever `memory.x` is changed,
 //! updating `memory.x` ensures a rebuild of the application with the
 //! new memory settings.
+//!
+//! It also converts the fonts and images in `assets/`, see [assets].
 
 use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
 
+#[path = "build/assets.rs"]
+mod assets;
+
+use assets::{Chars, FontSource, FontSpec, ImageSource, ImageSpec, Spacing};
+
+const FONTS: &[FontSpec] = &[
+    FontSpec {
+        ident: "SOURCE_CODE_PRO",
+        name: "source-code-pro",
+        source: FontSource::Png {
+            path: "assets/source-code-pro-ascii.png",
+            cols: 32,
+            rows: 3,
+        },
+        spacing: Spacing::Proportional { gap: 2, space: 7 },
+        chars: &[Chars::ASCII],
+    },
+    FontSpec {
+        ident: "SOURCE_CODE_PRO_HEAVY",
+        name: "source-code-pro-heavy",
+        source: FontSource::Png {
+            path: "assets/source-code-pro-heavy-ascii.png",
+            cols: 32,
+            rows: 3,
+        },
+        spacing: Spacing::Proportional { gap: 2, space: 7 },
+        chars: &[Chars::ASCII],
+    },
+    FontSpec {
+        ident: "PROFONT",
+        name: "profont",
+        source: FontSource::RawBits {
+            path: "ProFont24Point.raw",
+            cols: 32,
+            rows: 6,
+            cell_w: 16,
+            cell_h: 29,
+        },
+        spacing: Spacing::Mono,
+        // Cell 95 is DEL, then Latin-1 starts at cell 96. A no-break
+        // space looks just like a space.
+        chars: &[
+            Chars::ASCII,
+            Chars {
+                first: '\u{a0}',
+                last: '\u{a0}',
+                cell: 0,
+            },
+            Chars {
+                first: '¡',
+                last: 'ÿ',
+                cell: 96,
+            },
+        ],
+    },
+];
+
+const IMAGES: &[ImageSpec] = &[ImageSpec {
+    ident: "QUARTER_CIRCLE",
+    width: 120,
+    height: 120,
+    source: ImageSource::Png("assets/quarter-circle.png"),
+}];
+
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
@@ -28,6 +94,9 @@ fn main() {
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=build/assets.rs");
+
+    assets::convert_all(FONTS, IMAGES, out);
 
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
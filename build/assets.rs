@@ -0,0 +1,598 @@
+//! Converts the fonts and images under `assets/` into the formats the
+//! firmware draws from.
+//!
+//! Every source has its metrics checked here, so a wrong grid size or a
+//! truncated file is a build error rather than garbled glyphs on the screen.
+//! The output is a set of `.bin` files in `OUT_DIR`, plus `fonts.rs` and
+//! `images.rs` which are `include!`d by the firmware.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+/// Where the glyphs for a font come from
+pub enum FontSource {
+    /// A sheet of `cols` x `rows` cells of `cell_w` x `cell_h` pixels,
+    /// 1 bit per pixel, MSB first. Each row of pixels runs across the whole
+    /// sheet.
+    RawBits {
+        path: &'static str,
+        cols: usize,
+        rows: usize,
+        cell_w: usize,
+        cell_h: usize,
+    },
+    /// A PNG sheet of `cols` x `rows` cells. The cell size comes from the
+    /// image, which must divide evenly. Glyphs are taken
+    /// from the alpha channel if there is one, otherwise from the brightness.
+    Png {
+        path: &'static str,
+        cols: usize,
+        rows: usize,
+    },
+}
+
+pub struct FontSpec {
+    /// Name of the generated const
+    pub ident: &'static str,
+    /// Name shown by `fonts`
+    pub name: &'static str,
+    pub source: FontSource,
+    pub spacing: Spacing,
+    /// The characters to include. Anything else is drawn as a box.
+    pub chars: &'static [Chars],
+}
+
+/// A range of characters to take from a font
+pub struct Chars {
+    pub first: char,
+    pub last: char,
+    /// The cell holding `first`, with the rest following it
+    pub cell: usize,
+}
+
+impl Chars {
+    pub const ASCII: Self = Self {
+        first: ' ',
+        last: '~',
+        cell: 0,
+    };
+}
+
+/// How far apart to place the glyphs of a font
+pub enum Spacing {
+    /// Every glyph takes a whole cell
+    Mono,
+    /// Every glyph takes its own width plus `gap` pixels, and a space
+    /// takes `space` pixels
+    Proportional { gap: usize, space: usize },
+}
+
+pub enum ImageSource {
+    /// Any PNG, converted to RGB565
+    Png(&'static str),
+}
+
+pub struct ImageSpec {
+    pub ident: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub source: ImageSource,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bit,
+    Alpha,
+}
+
+/// A glyph as 8-bit alpha, row by row
+#[derive(Clone)]
+struct Glyph {
+    width: usize,
+    height: usize,
+    alpha: Vec<u8>,
+}
+
+/// A glyph trimmed to its ink, and where to draw it within the
+/// `advance` x line height box it takes up
+struct Placed {
+    left: usize,
+    top: usize,
+    advance: usize,
+    glyph: Glyph,
+}
+
+struct Converted {
+    kind: Kind,
+    cell_w: usize,
+    cell_h: usize,
+    /// By cell, left to right then top to bottom
+    cells: Vec<Glyph>,
+}
+
+pub fn convert_all(fonts: &[FontSpec], images: &[ImageSpec], out: &Path) {
+    let mut fonts_rs =
+        String::from("// Generated by build.rs from the font sources, do not edit\n\n");
+    let mut max_cell = 0;
+
+    for spec in fonts {
+        let conv = match &spec.source {
+            FontSource::RawBits {
+                path,
+                cols,
+                rows,
+                cell_w,
+                cell_h,
+            } => load_raw(path, Kind::Bit, *cols, *rows, *cell_w, *cell_h),
+            FontSource::Png { path, cols, rows } => load_png_sheet(path, *cols, *rows),
+        };
+        let mut glyphs = select(&conv, spec);
+        glyphs.push(replacement(&conv, spec, &glyphs));
+
+        let placed: Vec<_> = glyphs
+            .iter()
+            .map(|g| place(g, &spec.spacing, spec.name))
+            .collect();
+        let max_advance = placed.iter().map(|p| p.advance).max().unwrap_or(0);
+        assert!(
+            max_advance <= 255 && conv.cell_h <= 255,
+            "{}: glyphs must fit in 255x255px",
+            spec.name
+        );
+        max_cell = max_cell.max(max_advance * conv.cell_h * 2);
+
+        let bin_name = format!("font-{}.bin", spec.name);
+        let (data, offsets) = encode_glyphs(conv.kind, &placed);
+        fs::write(out.join(&bin_name), data).unwrap();
+
+        write_font(
+            &mut fonts_rs,
+            spec,
+            &conv,
+            max_advance,
+            &placed,
+            &offsets,
+            &bin_name,
+        );
+    }
+
+    let idents: Vec<_> = fonts.iter().map(|f| f.ident).collect();
+    writeln!(
+        fonts_rs,
+        "/// All fonts selectable with `font-select`, the first is the default\n\
+         pub const FONTS: &[Font] = &[{}];\n",
+        idents.join(", ")
+    )
+    .unwrap();
+    writeln!(
+        fonts_rs,
+        "/// Big enough for one character of any font in [FONTS]\n\
+         pub const CHAR_BUF_SIZE: usize = {max_cell};"
+    )
+    .unwrap();
+    fs::write(out.join("fonts.rs"), fonts_rs).unwrap();
+
+    let mut images_rs =
+        String::from("// Generated by build.rs from the image sources, do not edit\n\n");
+    for spec in images {
+        let data = load_image(spec);
+        let bin_name = format!("image-{}.bin", spec.ident.to_lowercase().replace('_', "-"));
+        fs::write(out.join(&bin_name), data).unwrap();
+        writeln!(
+            images_rs,
+            "pub const {}: Image = Image {{\n    \
+                 width: {},\n    \
+                 height: {},\n    \
+                 source: ImageSource::Static(include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{bin_name}\"))),\n\
+             }};\n",
+            spec.ident, spec.width, spec.height,
+        )
+        .unwrap();
+    }
+    fs::write(out.join("images.rs"), images_rs).unwrap();
+}
+
+fn source_path(path: &str) -> PathBuf {
+    println!("cargo:rerun-if-changed={path}");
+    PathBuf::from(path)
+}
+
+fn read_source(path: &str) -> Vec<u8> {
+    fs::read(source_path(path)).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+fn load_raw(
+    path: &str,
+    kind: Kind,
+    cols: usize,
+    rows: usize,
+    cell_w: usize,
+    cell_h: usize,
+) -> Converted {
+    let data = read_source(path);
+
+    let sheet_w = cols * cell_w;
+    let sheet_px = sheet_w * rows * cell_h;
+    let expected = match kind {
+        Kind::Bit => {
+            assert!(
+                sheet_w % 8 == 0,
+                "{path}: a {cols} x {cell_w}px sheet isn't a whole number of bytes wide"
+            );
+            sheet_px / 8
+        }
+        Kind::Alpha => sheet_px,
+    };
+    assert!(
+        data.len() == expected,
+        "{path}: {cols}x{rows} cells of {cell_w}x{cell_h}px should be {expected} bytes, found {}",
+        data.len(),
+    );
+
+    let pixel = |x: usize, y: usize| -> u8 {
+        match kind {
+            Kind::Bit => {
+                let byte = data[(y * sheet_w + x) / 8];
+                if byte & (0x80 >> (x % 8)) != 0 {
+                    255
+                } else {
+                    0
+                }
+            }
+            Kind::Alpha => data[y * sheet_w + x],
+        }
+    };
+
+    let cells = (0..cols * rows)
+        .map(|idx| cut_cell(idx, cols, cell_w, cell_h, pixel))
+        .collect();
+
+    Converted {
+        kind,
+        cell_w,
+        cell_h,
+        cells,
+    }
+}
+
+fn cut_cell(
+    idx: usize,
+    cols: usize,
+    cell_w: usize,
+    cell_h: usize,
+    pixel: impl Fn(usize, usize) -> u8,
+) -> Glyph {
+    let x0 = (idx % cols) * cell_w;
+    let y0 = (idx / cols) * cell_h;
+    let mut alpha = Vec::with_capacity(cell_w * cell_h);
+    for y in 0..cell_h {
+        for x in 0..cell_w {
+            alpha.push(pixel(x0 + x, y0 + y));
+        }
+    }
+    Glyph {
+        width: cell_w,
+        height: cell_h,
+        alpha,
+    }
+}
+
+/// Decode a PNG into 8-bit channels, returning (width, height, channels, data)
+fn decode_png(path: &str) -> (usize, usize, png::ColorType, Vec<u8>) {
+    let file = File::open(source_path(path)).unwrap_or_else(|e| panic!("{path}: {e}"));
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .unwrap_or_else(|e| panic!("{path}: {e}"));
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .unwrap_or_else(|e| panic!("{path}: {e}"));
+    buf.truncate(info.buffer_size());
+    (
+        info.width as usize,
+        info.height as usize,
+        info.color_type,
+        buf,
+    )
+}
+
+fn load_png_sheet(path: &str, cols: usize, rows: usize) -> Converted {
+    let (width, height, color, data) = decode_png(path);
+
+    assert!(
+        width % cols == 0 && height % rows == 0,
+        "{path}: {width}x{height}px doesn't divide into {cols}x{rows} cells"
+    );
+    let cell_w = width / cols;
+    let cell_h = height / rows;
+
+    let chans = color.samples();
+    let pixel = |x: usize, y: usize| -> u8 {
+        let px = &data[(y * width + x) * chans..][..chans];
+        match color {
+            png::ColorType::GrayscaleAlpha | png::ColorType::Rgba => px[chans - 1],
+            png::ColorType::Rgb => {
+                ((px[0] as u16 * 77 + px[1] as u16 * 150 + px[2] as u16 * 29) >> 8) as u8
+            }
+            _ => px[0],
+        }
+    };
+
+    let cells = (0..cols * rows)
+        .map(|idx| cut_cell(idx, cols, cell_w, cell_h, pixel))
+        .collect();
+
+    Converted {
+        kind: Kind::Alpha,
+        cell_w,
+        cell_h,
+        cells,
+    }
+}
+
+/// Pick out the glyphs for `spec.chars`, in order
+fn select(conv: &Converted, spec: &FontSpec) -> Vec<Glyph> {
+    let mut glyphs = Vec::new();
+    for range in spec.chars {
+        assert!(
+            range.first <= range.last,
+            "{}: {:?}..={:?} is backwards",
+            spec.name,
+            range.first,
+            range.last
+        );
+        for (i, ch) in (range.first..=range.last).enumerate() {
+            let glyph = conv.cells.get(range.cell + i).unwrap_or_else(|| {
+                panic!("{}: no glyph for {ch:?} (U+{:04X})", spec.name, ch as u32)
+            });
+            glyphs.push(glyph.clone());
+        }
+    }
+
+    for (i, a) in spec.chars.iter().enumerate() {
+        for b in &spec.chars[i + 1..] {
+            assert!(
+                a.last < b.first || b.last < a.first,
+                "{}: {:?}..={:?} overlaps {:?}..={:?}",
+                spec.name,
+                a.first,
+                a.last,
+                b.first,
+                b.last,
+            );
+        }
+    }
+
+    glyphs
+}
+
+/// An outlined box, drawn for characters the font doesn't have. It's the
+/// size of an `M` if there is one, or most of the cell if not.
+fn replacement(conv: &Converted, spec: &FontSpec, glyphs: &[Glyph]) -> Glyph {
+    let (w, h) = (conv.cell_w, conv.cell_h);
+
+    let m = spec
+        .chars
+        .iter()
+        .scan(0, |idx, range| {
+            let start = *idx;
+            *idx += (range.first..=range.last).count();
+            Some((start, range))
+        })
+        .find(|(_, range)| (range.first..=range.last).contains(&'M'))
+        .map(|(start, range)| &glyphs[start + ('M' as usize - range.first as usize)]);
+
+    let ink = |g: &Glyph, x: usize, y: usize| g.alpha[y * g.width + x] != 0;
+    let bounds = m.and_then(|g| {
+        let xs: Vec<_> = (0..w).filter(|x| (0..h).any(|y| ink(g, *x, y))).collect();
+        let ys: Vec<_> = (0..h).filter(|y| (0..w).any(|x| ink(g, x, *y))).collect();
+        Some((*xs.first()?, *xs.last()?, *ys.first()?, *ys.last()?))
+    });
+    let (x0, x1, y0, y1) = bounds.unwrap_or((w / 8, w - 1 - w / 8, h / 4, h - 1 - h / 4));
+
+    let thick = (w / 8).max(1);
+    let mut alpha = vec![0u8; w * h];
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let edge = x < x0 + thick || x + thick > x1 || y < y0 + thick || y + thick > y1;
+            if edge {
+                alpha[y * w + x] = 255;
+            }
+        }
+    }
+
+    Glyph {
+        width: w,
+        height: h,
+        alpha,
+    }
+}
+
+/// Trim `glyph` down to the pixels that are drawn, and work out its
+/// spacing. Nothing is moved vertically, so every glyph keeps its place
+/// relative to the baseline.
+fn place(glyph: &Glyph, spacing: &Spacing, name: &str) -> Placed {
+    let ink = |x: usize, y: usize| glyph.alpha[y * glyph.width + x] != 0;
+    let cols: Vec<_> = (0..glyph.width)
+        .filter(|x| (0..glyph.height).any(|y| ink(*x, y)))
+        .collect();
+    let rows: Vec<_> = (0..glyph.height)
+        .filter(|y| (0..glyph.width).any(|x| ink(x, *y)))
+        .collect();
+
+    let (Some(x0), Some(x1), Some(y0), Some(y1)) =
+        (cols.first(), cols.last(), rows.first(), rows.last())
+    else {
+        // Nothing drawn, like a space
+        let advance = match spacing {
+            Spacing::Mono => glyph.width,
+            Spacing::Proportional { space, .. } => *space,
+        };
+        return Placed {
+            left: 0,
+            top: 0,
+            advance,
+            glyph: Glyph {
+                width: 0,
+                height: 0,
+                alpha: Vec::new(),
+            },
+        };
+    };
+
+    let width = x1 - x0 + 1;
+    let height = y1 - y0 + 1;
+    let mut alpha = Vec::with_capacity(width * height);
+    for y in *y0..=*y1 {
+        alpha.extend_from_slice(&glyph.alpha[y * glyph.width..][*x0..=*x1]);
+    }
+
+    let (left, advance) = match spacing {
+        Spacing::Mono => (*x0, glyph.width),
+        Spacing::Proportional { gap, .. } => (gap / 2, width + gap),
+    };
+    assert!(
+        left + width <= advance,
+        "{name}: a {width}px wide glyph doesn't fit in its {advance}px advance"
+    );
+
+    Placed {
+        left,
+        top: *y0,
+        advance,
+        glyph: Glyph {
+            width,
+            height,
+            alpha,
+        },
+    }
+}
+
+/// Pack every glyph back to back, returning the data and each glyph's offset
+fn encode_glyphs(kind: Kind, placed: &[Placed]) -> (Vec<u8>, Vec<usize>) {
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+
+    for Placed { glyph, .. } in placed {
+        offsets.push(data.len());
+        match kind {
+            Kind::Bit => {
+                // Each row padded out to a whole byte, MSB first
+                for row in glyph.alpha.chunks(glyph.width.max(1)) {
+                    for byte_px in row.chunks(8) {
+                        let mut byte = 0u8;
+                        for (i, a) in byte_px.iter().enumerate() {
+                            if *a >= 0x80 {
+                                byte |= 0x80 >> i;
+                            }
+                        }
+                        data.push(byte);
+                    }
+                }
+            }
+            Kind::Alpha => data.extend_from_slice(&glyph.alpha),
+        }
+    }
+
+    (data, offsets)
+}
+
+fn write_font(
+    out: &mut String,
+    spec: &FontSpec,
+    conv: &Converted,
+    max_advance: usize,
+    placed: &[Placed],
+    offsets: &[usize],
+    bin: &str,
+) {
+    // The replacement is always last
+    let replacement = placed.len() - 1;
+    assert!(
+        replacement <= u16::MAX as usize,
+        "{}: too many glyphs",
+        spec.name
+    );
+
+    let kind = match conv.kind {
+        Kind::Bit => "FontKind::Bit",
+        Kind::Alpha => "FontKind::Alpha",
+    };
+
+    writeln!(out, "pub const {}: Font = Font {{", spec.ident).unwrap();
+    writeln!(out, "    name: {:?},", spec.name).unwrap();
+    writeln!(out, "    kind: {kind},").unwrap();
+    writeln!(
+        out,
+        "    monospace: {},",
+        matches!(spec.spacing, Spacing::Mono)
+    )
+    .unwrap();
+    writeln!(out, "    char_width_px: {max_advance},").unwrap();
+    writeln!(out, "    char_height_px: {},", conv.cell_h).unwrap();
+    writeln!(out, "    chars: &[").unwrap();
+    let mut glyph = 0;
+    for range in spec.chars {
+        writeln!(
+            out,
+            "        CharRange {{ first: {:#x}, last: {:#x}, glyph: {glyph} }},",
+            range.first as u32, range.last as u32,
+        )
+        .unwrap();
+        glyph += (range.first..=range.last).count();
+    }
+    writeln!(out, "    ],").unwrap();
+    writeln!(out, "    replacement: {replacement},").unwrap();
+    writeln!(out, "    glyphs: &[").unwrap();
+    for (p, offset) in placed.iter().zip(offsets) {
+        writeln!(
+            out,
+            "        Glyph {{ offset: {offset}, left: {}, top: {}, width: {}, height: {}, advance: {} }},",
+            p.left, p.top, p.glyph.width, p.glyph.height, p.advance,
+        )
+        .unwrap();
+    }
+    writeln!(out, "    ],").unwrap();
+    writeln!(
+        out,
+        "    data: include_bytes!(concat!(env!(\"OUT_DIR\"), \"/{bin}\")),"
+    )
+    .unwrap();
+    writeln!(out, "}};\n").unwrap();
+}
+
+fn load_image(spec: &ImageSpec) -> Vec<u8> {
+    assert!(
+        spec.width <= 240 && spec.height <= 240,
+        "{}: {}x{} is bigger than the screen",
+        spec.ident,
+        spec.width,
+        spec.height
+    );
+
+    let ImageSource::Png(path) = spec.source;
+    let (width, height, color, data) = decode_png(path);
+    assert!(
+        (width, height) == (spec.width, spec.height),
+        "{path}: expected {}x{}, found {width}x{height}",
+        spec.width,
+        spec.height
+    );
+
+    let chans = color.samples();
+    data.chunks_exact(chans)
+        .flat_map(|px| {
+            let (r, g, b) = match color {
+                png::ColorType::Rgb | png::ColorType::Rgba => (px[0], px[1], px[2]),
+                _ => (px[0], px[0], px[0]),
+            };
+            let val = ((r as u16 & 0xF8) << 8) | ((g as u16 & 0xFC) << 3) | (b as u16 >> 3);
+            val.to_be_bytes()
+        })
+        .collect()
+}
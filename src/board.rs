@@ -0,0 +1,57 @@
+//! What's wired where
+//!
+//! Every pin and peripheral assignment lives here, one module per board,
+//! picked with a `board-...` cargo feature. `main` builds everything from
+//! [Board::new], so a new board revision or a hand-wired clone only needs a
+//! new module and feature, not changes all over the firmware.
+//!
+//! Each board module provides:
+//!
+//! * type aliases for the peripherals that show up in driver types, like
+//!   [LcdSpi] or [Led1Pwm]
+//! * which half of its PWM slice each PWM output is on
+//! * `Board::new`, building the drivers from the peripherals
+
+use embassy_rp::pwm;
+
+#[cfg(not(any(feature = "board-rev1")))]
+compile_error!("pick a board with one of the `board-...` features");
+
+#[cfg(feature = "board-rev1")]
+mod rev1;
+#[cfg(feature = "board-rev1")]
+pub use rev1::*;
+
+/// Which pin of a PWM slice an output is on
+#[derive(Clone, Copy)]
+pub enum PwmOutput {
+    A,
+    B,
+}
+
+impl PwmOutput {
+    /// A config running at `duty` out of 65535 on this output
+    pub fn config(self, duty: u16) -> pwm::Config {
+        let mut config = pwm::Config::default();
+        config.top = u16::MAX;
+        self.set_compare(&mut config, duty);
+        config.enable = true;
+        config
+    }
+
+    /// Set this output's compare value in `config`, leaving the other alone
+    pub fn set_compare(self, config: &mut pwm::Config, compare: u16) {
+        match self {
+            PwmOutput::A => config.compare_a = compare,
+            PwmOutput::B => config.compare_b = compare,
+        }
+    }
+
+    /// Set whether this output is inverted in `config`
+    pub fn set_invert(self, config: &mut pwm::Config, invert: bool) {
+        match self {
+            PwmOutput::A => config.invert_a = invert,
+            PwmOutput::B => config.invert_b = invert,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! The original badge
+//!
+//! * 00 - (EXT) LED3 - PWM0A
+//! * 01 - (EXT) SW1
+//! * 02 - N/A
+//! * 03 - N/A
+//! * 04 - (EXT) SPI0 IO3
+//! * 05 - (EXT) SPI0 IO2
+//! * 06 - (BRD) IMU/I2C SDA
+//! * 07 - (BRD) IMU/I2C SCL
+//! * 08 - (BRD) LCD D/C
+//! * 09 - (BRD) LCD CSn
+//! * 10 - (BRD) LCD CLK
+//! * 11 - (BRD) LCD DIN
+//! * 12 - (BRD) LCD Reset
+//! * 13 - (EXT) SW2
+//! * 14 - (EXT) LED4 - PWM7A
+//! * 15 - (EXT) SW3
+//! * 16 - (EXT) SPI0 RX
+//! * 17 - (EXT) SPI0 CSn
+//! * 18 - (EXT) SPI0 SCK
+//! * 19 - (EXT) SPI0 TX
+//! * 20 - (EXT) /!\ SPI0 PWR /!\
+//! * 21 - (EXT) SW6
+//! * 22 - (EXT) LED1 - PWM3A
+//! * 23 - (BRD) IMU INT1
+//! * 24 - (BRD) IMU INT2
+//! * 25 - (BRD) LCD Backlight
+//! * 26 - (EXT) SW4
+//! * 27 - (EXT) LED2 - PWM5B
+//! * 28 - (EXT) SW5
+//! * 29 - (BRD) Battery ADC
+//!
+//! With the `buzzer` feature a piezo on 22 takes PWM3A from LED1, and
+//! DMA_CH3 for sample playback.
+//!
+//! With the `smartled` feature the SPI0 port has a WS2812 strip instead of
+//! the flash, data on 19 and the strip's supply switched by 20.
+
+use embassy_rp::{
+    adc::{self, Adc},
+    gpio::{AnyPin, Input, Level, Output, Pull},
+    peripherals::{PWM_CH0, PWM_CH3, PWM_CH4, PWM_CH5, PWM_CH7, SPI0, SPI1, USB},
+    pwm::{self, Pwm},
+    spi::{self, Spi},
+    Peripherals,
+};
+
+use super::PwmOutput;
+use crate::{
+    buttons::Buttons,
+    dial,
+    lcd::{Backlight, LcdPins, LcdState, Orientation},
+    leds::Leds,
+    shadow::Shadow,
+    smartled::StripInfo,
+    spiflash::SpiFlash,
+};
+
+pub type LcdSpi = SPI1;
+pub type FlashSpi = SPI0;
+pub type BacklightPwm = PWM_CH4;
+pub type Led1Pwm = PWM_CH3;
+pub type Led2Pwm = PWM_CH5;
+pub type Led3Pwm = PWM_CH0;
+pub type Led4Pwm = PWM_CH7;
+/// Shared with LED1, see the `buzzer` feature
+#[cfg(feature = "buzzer")]
+pub type BuzzerPwm = PWM_CH3;
+
+pub const BACKLIGHT_OUTPUT: PwmOutput = PwmOutput::B;
+/// LED1 to LED4
+pub const LED_OUTPUTS: [PwmOutput; 4] = [PwmOutput::A, PwmOutput::B, PwmOutput::A, PwmOutput::A];
+#[cfg(feature = "buzzer")]
+pub const BUZZER_OUTPUT: PwmOutput = PwmOutput::A;
+/// [BuzzerPwm]'s number, for DMA straight into its registers
+#[cfg(feature = "buzzer")]
+pub const BUZZER_SLICE: usize = 3;
+/// The strip on the SPI0 port, with the `smartled` feature
+#[cfg(feature = "smartled")]
+pub const SMARTLED_STRIPS: &[StripInfo] = &[StripInfo {
+    len: 8,
+    format: crate::ws2812::Format::new("GRB"),
+}];
+#[cfg(not(feature = "smartled"))]
+pub const SMARTLED_STRIPS: &[StripInfo] = &[];
+
+pub struct Board {
+    pub usb: USB,
+    pub lcd: LcdPins,
+    pub leds: Leds,
+    pub buttons: Buttons,
+    pub adc: Adc<'static, adc::Async>,
+    /// The analog input read by [crate::dial]
+    pub dial: adc::Channel<'static>,
+    /// `None` when the SPI0 port is used for something else
+    pub flash: Option<SpiFlash>,
+    #[cfg(feature = "smartled")]
+    pub smartled: crate::smartled::Strips,
+    #[cfg(feature = "buzzer")]
+    pub buzzer: crate::buzzer::Buzzer,
+}
+
+impl Board {
+    pub fn new(p: Peripherals) -> Self {
+        let mut lcd_cfg = spi::Config::default();
+        lcd_cfg.frequency = 62_500_000;
+        let bl = Pwm::new_output_b(p.PWM_CH4, p.PIN_25, pwm::Config::default());
+
+        let lcd = LcdPins {
+            spi: Spi::new_txonly(p.SPI1, p.PIN_10, p.PIN_11, p.DMA_CH0, lcd_cfg),
+            dc: Output::new(AnyPin::from(p.PIN_8), Level::Low),
+            cs: Output::new(AnyPin::from(p.PIN_9), Level::High),
+            rst: Output::new(AnyPin::from(p.PIN_12), Level::High),
+            backlight: Backlight::new(bl),
+            orientation: Orientation::default(),
+            state: LcdState::Uninit,
+            shadow: Shadow::take().unwrap(),
+        };
+
+        // LED1 and the buzzer share a pin and a slice
+        #[cfg(not(feature = "buzzer"))]
+        let led_1 = Some(Pwm::new_output_a(
+            p.PWM_CH3,
+            p.PIN_22,
+            pwm::Config::default(),
+        ));
+        #[cfg(feature = "buzzer")]
+        let led_1 = None;
+        #[cfg(feature = "buzzer")]
+        let buzzer = crate::buzzer::Buzzer::new(
+            Pwm::new_output_a(p.PWM_CH3, p.PIN_22, pwm::Config::default()),
+            p.DMA_CH3,
+        );
+
+        let leds = Leds::new(
+            led_1,
+            Pwm::new_output_b(p.PWM_CH5, p.PIN_27, pwm::Config::default()),
+            Pwm::new_output_a(p.PWM_CH0, p.PIN_0, pwm::Config::default()),
+            Pwm::new_output_a(p.PWM_CH7, p.PIN_14, pwm::Config::default()),
+        );
+
+        let buttons = Buttons {
+            a: Input::new(AnyPin::from(p.PIN_1), Pull::Up),
+            b: Input::new(AnyPin::from(p.PIN_13), Pull::Up),
+            c: Input::new(AnyPin::from(p.PIN_15), Pull::Up),
+            d: Input::new(AnyPin::from(p.PIN_26), Pull::Up),
+            e: Input::new(AnyPin::from(p.PIN_28), Pull::Up),
+            f: Input::new(AnyPin::from(p.PIN_21), Pull::Up),
+        };
+
+        let adc = Adc::new(p.ADC, dial::Irqs, adc::Config::default());
+        let dial = adc::Channel::new_pin(p.PIN_29, Pull::None);
+
+        #[cfg(not(feature = "smartled"))]
+        let flash = {
+            let mut flash_cfg = spi::Config::default();
+            flash_cfg.frequency = 16_000_000;
+            Some(SpiFlash {
+                spi: Spi::new(
+                    p.SPI0,    // Periph
+                    p.PIN_18,  // SCK
+                    p.PIN_19,  // MOSI
+                    p.PIN_16,  // MISO
+                    p.DMA_CH1, // TX DMA
+                    p.DMA_CH2, // RX DMA
+                    flash_cfg,
+                ),
+                csn: Output::new(AnyPin::from(p.PIN_17), Level::High),
+                io2: Input::new(AnyPin::from(p.PIN_5), Pull::None),
+                io3: Input::new(AnyPin::from(p.PIN_4), Pull::None),
+            })
+        };
+
+        #[cfg(feature = "smartled")]
+        let flash = None;
+        #[cfg(feature = "smartled")]
+        let smartled = {
+            use crate::ws2812::{self, Ws2812};
+            use embassy_rp::pio::Pio;
+
+            let Pio {
+                mut common, sm0, ..
+            } = Pio::new(p.PIO0, ws2812::Irqs);
+            let pow = Output::new(AnyPin::from(p.PIN_20), Level::Low);
+            let format = SMARTLED_STRIPS[0].format;
+            let strip = Ws2812::new(&mut common, sm0, p.DMA_CH1, p.PIN_19, Some(pow), format);
+
+            let mut strips = crate::smartled::Strips::new(common);
+            strips.add(strip);
+            strips
+        };
+
+        Self {
+            usb: p.USB,
+            lcd,
+            leds,
+            buttons,
+            adc,
+            dial,
+            flash,
+            #[cfg(feature = "smartled")]
+            smartled,
+            #[cfg(feature = "buzzer")]
+            buzzer,
+        }
+    }
+}
@@ -1,8 +1,69 @@
-use embassy_rp::{gpio::{Input, AnyPin}, pwm};
-use embassy_time::{Timer, Duration};
+//! The six buttons
+//!
+//! [butt] sleeps until a pin interrupt, debounces each button on its own,
+//! and turns what it sees into [ButtonEvent]s on [BUTTON_EVENTS]. Holding a
+//! button gives a long press and then auto-repeats, and two quick presses
+//! give a double click as well as the two presses.
 
-use crate::{buzzer::Pwim, forth::OUTPIPE};
+use embassy_futures::select::{select, select_array};
+use embassy_rp::gpio::{AnyPin, Input};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::{Duration, Instant, Timer};
 
+use crate::{
+    forth::{OUTPIPE, OUTPIPE_LOCK},
+    power::BUTTON_ACTIVITY,
+};
+
+/// How long a button has to stay put before a change counts
+const DEBOUNCE: Duration = Duration::from_millis(20);
+/// Held this long for a long press
+const LONG_PRESS: Duration = Duration::from_millis(500);
+/// Time between repeats once a long press has started
+const REPEAT: Duration = Duration::from_millis(100);
+/// Most time from a release to the next press for a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(300);
+/// Read the pins this often even without an interrupt, in case an edge
+/// came while nothing was waiting for it
+const RESYNC: Duration = Duration::from_secs(1);
+
+/// The six buttons, SW1 to SW6
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Button {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl Button {
+    pub const ALL: [Button; 6] = [
+        Button::A,
+        Button::B,
+        Button::C,
+        Button::D,
+        Button::E,
+        Button::F,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ButtonEvent {
+    Pressed(Button),
+    Released(Button),
+    /// Held for a while, after `Pressed`
+    LongPress(Button),
+    /// Still held after a long press, every so often
+    Repeat(Button),
+    /// Pressed again soon after being released, after the second `Pressed`
+    DoubleClick(Button),
+}
+
+/// Every event, for whoever is listening. Events are dropped when nobody
+/// keeps up, so listeners should drain it before they start.
+pub static BUTTON_EVENTS: Channel<ThreadModeRawMutex, ButtonEvent, 16> = Channel::new();
 
 pub struct Buttons {
     pub a: Input<'static, AnyPin>,
@@ -24,40 +85,142 @@ impl Buttons {
             self.f.is_low(),
         ]
     }
+
+    /// Wait for any pin to change, or `until`
+    async fn wait(&mut self, until: Instant) {
+        let pins = [
+            &mut self.a,
+            &mut self.b,
+            &mut self.c,
+            &mut self.d,
+            &mut self.e,
+            &mut self.f,
+        ];
+        let edges = select_array(pins.map(|pin| pin.wait_for_any_edge()));
+        select(edges, Timer::at(until)).await;
+    }
+}
+
+/// One button's debouncing and timing
+struct Tracker {
+    /// Last level read, and when it was first read like that
+    raw: bool,
+    changed: Instant,
+    /// The debounced state
+    down: bool,
+    /// When the next long press or repeat is due, while down
+    hold: Option<Instant>,
+    /// Whether this press has long pressed
+    long: bool,
+    /// When a click that could start a double click was released
+    clicked: Option<Instant>,
+    /// Whether this press was the second half of a double click
+    double: bool,
 }
 
+impl Tracker {
+    fn new(raw: bool, now: Instant) -> Self {
+        Self {
+            raw,
+            changed: now,
+            down: raw,
+            hold: None,
+            long: false,
+            clicked: None,
+            double: false,
+        }
+    }
+
+    /// Take a new reading, and report whatever that or the time means
+    fn update(
+        &mut self,
+        button: Button,
+        raw: bool,
+        now: Instant,
+        mut emit: impl FnMut(ButtonEvent),
+    ) {
+        if raw != self.raw {
+            self.raw = raw;
+            self.changed = now;
+        }
+
+        if self.raw != self.down && now >= self.changed + DEBOUNCE {
+            self.down = self.raw;
+            if self.down {
+                emit(ButtonEvent::Pressed(button));
+                self.double = self
+                    .clicked
+                    .take()
+                    .map(|at| now - at <= DOUBLE_CLICK)
+                    .unwrap_or(false);
+                if self.double {
+                    emit(ButtonEvent::DoubleClick(button));
+                }
+                self.hold = Some(now + LONG_PRESS);
+                self.long = false;
+            } else {
+                emit(ButtonEvent::Released(button));
+                // A long press or a double click doesn't start another
+                self.clicked = (!self.long && !self.double).then_some(now);
+                self.hold = None;
+            }
+        }
+
+        if let Some(at) = self.hold.filter(|at| now >= *at) {
+            emit(if self.long {
+                ButtonEvent::Repeat(button)
+            } else {
+                ButtonEvent::LongPress(button)
+            });
+            self.long = true;
+            // From when it was due, so repeats stay evenly spaced
+            self.hold = Some(at + REPEAT);
+        }
+    }
+
+    /// When [Tracker::update] next has something to do without a new edge
+    fn deadline(&self) -> Option<Instant> {
+        if self.raw != self.down {
+            Some(self.changed + DEBOUNCE)
+        } else {
+            self.hold
+        }
+    }
+}
 
 #[embassy_executor::task]
-pub async fn butt(
-    btn: Buttons,
-    // mut p: Pwim
-) {
-    let mut state = btn.read_all();
+pub async fn butt(mut btn: Buttons) {
+    let now = Instant::now();
+    let mut trackers = btn.read_all().map(|raw| Tracker::new(raw, now));
     loop {
-        let new_state = btn.read_all();
-        if new_state != state {
-            // if new_state[0] {
-            //     let mut c: pwm::Config = Default::default();
-            //     c.top = 0x8000;
-            //     c.compare_a = 0x4000;
-            //     p.pwm.set_config(&c);
-            // } else {
-            //     let mut c: pwm::Config = Default::default();
-            //     c.top = 0x8000;
-            //     c.compare_a = 0;
-            //     p.pwm.set_config(&c);
-            // }
+        let until = trackers
+            .iter()
+            .filter_map(Tracker::deadline)
+            .min()
+            .unwrap_or_else(|| Instant::now() + RESYNC);
+        btn.wait(until).await;
+
+        let now = Instant::now();
+        let mut changed = false;
+        for ((tracker, raw), button) in trackers.iter_mut().zip(btn.read_all()).zip(Button::ALL) {
+            if raw != tracker.raw {
+                BUTTON_ACTIVITY.signal(());
+            }
+            tracker.update(button, raw, now, |event| {
+                changed |= matches!(event, ButtonEvent::Pressed(_) | ButtonEvent::Released(_));
+                BUTTON_EVENTS.try_send(event).ok();
+            });
+        }
+
+        if changed {
+            let _out = OUTPIPE_LOCK.lock().await;
             OUTPIPE.write_all(b"\r\n").await;
-            for b in new_state.iter().copied() {
-                OUTPIPE.write_all(if b {
-                    b"X"
-                } else {
-                    b"_"
-                }).await;
+            for tracker in trackers.iter() {
+                OUTPIPE
+                    .write_all(if tracker.down { b"X" } else { b"_" })
+                    .await;
             }
             OUTPIPE.write_all(b"\r\n").await;
-            state = new_state;
         }
-        Timer::after(Duration::from_millis(50)).await;
     }
 }
@@ -1,6 +1,279 @@
-use embassy_rp::{pwm::Pwm, peripherals::PWM_CH3};
+//! A piezo buzzer
+//!
+//! The buzzer sits on LED1's pin, so it's only fitted with the `buzzer`
+//! feature, which hands that PWM slice over from [crate::leds] to here.
+//! Like the LEDs it's owned by the [run] task and driven by sending it a
+//! [BuzzerCmd], so tones stop on time while the REPL does other things.
+//! Whole tunes are handed over with [play], and play out the same way.
+//!
+//! It can also play 8-bit unsigned PCM, as a PWM DAC. The slice runs at
+//! sys/256, far above hearing, and a DMA channel paced by a DMA timer
+//! writes each sample into the compare register at the sample rate.
 
+#[cfg(feature = "buzzer")]
+use embassy_futures::{
+    join::join,
+    select::{select, select3, Either, Either3},
+};
+#[cfg(feature = "buzzer")]
+use embassy_rp::{
+    dma::{self, AnyChannel},
+    into_ref, pac,
+    pwm::Pwm,
+    Peripheral, PeripheralRef,
+};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, pipe::Pipe, signal::Signal,
+};
+#[cfg(feature = "buzzer")]
+use embassy_time::{with_timeout, Duration, Instant, Timer};
 
-pub struct Pwim {
-    pub pwm: Pwm<'static, PWM_CH3>,
+#[cfg(feature = "buzzer")]
+use crate::board;
+use crate::rtttl::{self, Melody};
+
+/// Whether this build has a buzzer at all
+pub const FITTED: bool = cfg!(feature = "buzzer");
+
+/// Commands waiting for [run]
+pub static BUZZER_CMDS: Channel<ThreadModeRawMutex, BuzzerCmd, 8> = Channel::new();
+
+/// The next tune for [run], replacing any that hasn't started yet
+static MELODY: Signal<ThreadModeRawMutex, Melody> = Signal::new();
+
+/// Samples for [Samples::Stream], written by whoever sent the command
+pub static PCM_STREAM: Pipe<ThreadModeRawMutex, 1024> = Pipe::new();
+
+/// A stream that goes quiet this long is over
+#[cfg(feature = "buzzer")]
+const STREAM_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Samples sent by DMA at a time, while the next lot is fetched
+#[cfg(feature = "buzzer")]
+const CHUNK: usize = 256;
+
+/// DMA timer 0 as a DMA request
+#[cfg(feature = "buzzer")]
+const DREQ_TIMER0: u8 = 0x3b;
+
+// Only read by [run], which needs the `buzzer` feature
+#[cfg_attr(not(feature = "buzzer"), allow(dead_code))]
+#[derive(Clone, Copy)]
+pub enum BuzzerCmd {
+    /// Sound `hz` for `ms` milliseconds, or until told otherwise if 0
+    Tone { hz: u32, ms: u32 },
+    /// Go quiet, stopping any tune or samples
+    Off,
+    /// Play 8-bit unsigned samples at `rate` per second
+    Pcm { samples: Samples, rate: u32 },
+}
+
+/// Where samples come from
+#[cfg_attr(not(feature = "buzzer"), allow(dead_code))]
+#[derive(Clone, Copy)]
+pub enum Samples {
+    /// This many bytes through [PCM_STREAM]. Nothing else is handled until
+    /// they've all arrived, or the stream stalls.
+    Stream(u32),
+}
+
+#[cfg(feature = "buzzer")]
+impl Samples {
+    /// Fill `buf` with the next samples, returning how many. 0 is the end.
+    async fn next(&mut self, buf: &mut [u16; CHUNK]) -> usize {
+        let mut bytes = [0u8; CHUNK];
+        let n = match self {
+            Samples::Stream(left) => {
+                let want = (*left as usize).min(CHUNK);
+                let mut got = 0;
+                while got < want {
+                    let read = PCM_STREAM.read(&mut bytes[got..want]);
+                    match with_timeout(STREAM_TIMEOUT, read).await {
+                        Ok(n) => got += n,
+                        Err(_) => break,
+                    }
+                }
+                *left = if got < want { 0 } else { *left - got as u32 };
+                got
+            }
+        };
+        for (dst, b) in buf.iter_mut().zip(bytes[..n].iter()) {
+            *dst = *b as u16;
+        }
+        n
+    }
+}
+
+/// Start playing an RTTTL tune, or a list of notes, in the background
+pub fn play(text: &[u8]) -> Result<(), ()> {
+    if !FITTED {
+        return Err(());
+    }
+    MELODY.signal(rtttl::parse(text)?);
+    Ok(())
+}
+
+#[cfg(feature = "buzzer")]
+pub struct Buzzer {
+    pwm: Pwm<'static, board::BuzzerPwm>,
+    /// Feeds samples to the PWM
+    dma: PeripheralRef<'static, AnyChannel>,
+}
+
+#[cfg(feature = "buzzer")]
+impl Buzzer {
+    pub fn new(
+        pwm: Pwm<'static, board::BuzzerPwm>,
+        dma: impl Peripheral<P = impl dma::Channel> + 'static,
+    ) -> Self {
+        into_ref!(dma);
+        let mut buzzer = Self {
+            pwm,
+            dma: dma.map_into(),
+        };
+        buzzer.off();
+        buzzer
+    }
+
+    /// A square wave at `hz`, as near as the divider gets
+    pub fn tone(&mut self, hz: u32) {
+        if hz == 0 {
+            return self.off();
+        }
+
+        // Use the smallest divider that fits a period in the counter, for
+        // the finest steps in frequency
+        let cycles = embassy_rp::clocks::clk_sys_freq() as u64 * 16 / hz as u64;
+        let div = ((cycles + 0xFFFF) >> 16).clamp(16, 255 * 16);
+        let top = (cycles / div).clamp(2, 0x10000) - 1;
+
+        let mut config = board::BUZZER_OUTPUT.config(((top + 1) / 2) as u16);
+        config.top = top as u16;
+        config.divider = fixed::FixedU16::from_bits(div as u16);
+        self.pwm.set_config(&config);
+    }
+
+    /// Hold the pin low, rather than stopping the slice wherever it was
+    pub fn off(&mut self) {
+        self.pwm.set_config(&board::BUZZER_OUTPUT.config(0));
+    }
+
+    /// Play `samples` until they run out
+    pub async fn pcm(&mut self, mut samples: Samples, rate: u32) {
+        // One count per sample value, resting in the middle
+        let mut config = board::BUZZER_OUTPUT.config(128);
+        config.top = 255;
+        self.pwm.set_config(&config);
+
+        // The timer ticks at sys * x / y, pick the biggest x that fits
+        let sys = embassy_rp::clocks::clk_sys_freq() as u64;
+        let rate = (rate as u64).clamp(1, sys / 4);
+        let x = (0xFFFF * rate / sys).clamp(1, 0xFFFF);
+        let y = ((sys * x + rate / 2) / rate).min(0xFFFF);
+        pac::DMA.timer(0).write(|w| {
+            w.set_x(x as u16);
+            w.set_y(y as u16);
+        });
+
+        // Narrow writes are copied across the whole register, so this sets
+        // both compare values. Nothing else is on the buzzer's slice.
+        let cc = pac::PWM.ch(board::BUZZER_SLICE).cc().as_ptr() as *mut u16;
+
+        let mut bufs = [[0u16; CHUNK]; 2];
+        let mut len = samples.next(&mut bufs[0]).await;
+        let mut playing = 0;
+        while len > 0 {
+            let (first, second) = bufs.split_at_mut(1);
+            let (now, next) = if playing == 0 {
+                (&first[0], &mut second[0])
+            } else {
+                (&second[0], &mut first[0])
+            };
+            // Safety: `now` isn't touched until the transfer is done
+            let transfer = unsafe { dma::write(self.dma.reborrow(), &now[..len], cc, DREQ_TIMER0) };
+            let ((), next_len) = join(transfer, samples.next(next)).await;
+            len = next_len;
+            playing ^= 1;
+        }
+
+        self.off();
+    }
+}
+
+/// What woke up [run]
+#[cfg(feature = "buzzer")]
+enum Wake {
+    Cmd(BuzzerCmd),
+    Melody(Melody),
+    Timeout,
+}
+
+#[cfg(feature = "buzzer")]
+#[embassy_executor::task]
+pub async fn run(mut buzzer: Buzzer) {
+    // When the current tone or note is over
+    let mut deadline: Option<Instant> = None;
+    // The tune playing, and how far through. Each note is two steps, the
+    // note and a short gap so repeats don't run together.
+    let mut melody: Option<(Melody, usize)> = None;
+
+    loop {
+        let wake = match deadline {
+            Some(at) => match select3(BUZZER_CMDS.receive(), MELODY.wait(), Timer::at(at)).await {
+                Either3::First(cmd) => Wake::Cmd(cmd),
+                Either3::Second(tune) => Wake::Melody(tune),
+                Either3::Third(()) => Wake::Timeout,
+            },
+            None => match select(BUZZER_CMDS.receive(), MELODY.wait()).await {
+                Either::First(cmd) => Wake::Cmd(cmd),
+                Either::Second(tune) => Wake::Melody(tune),
+            },
+        };
+
+        match wake {
+            Wake::Cmd(BuzzerCmd::Tone { hz, ms }) => {
+                melody = None;
+                buzzer.tone(hz);
+                deadline = (ms != 0).then(|| Instant::now() + Duration::from_millis(ms as u64));
+            }
+            Wake::Cmd(BuzzerCmd::Off) => {
+                melody = None;
+                buzzer.off();
+                deadline = None;
+            }
+            Wake::Cmd(BuzzerCmd::Pcm { samples, rate }) => {
+                melody = None;
+                deadline = None;
+                // Whoever is streaming is waiting on us
+                buzzer.pcm(samples, rate).await;
+            }
+            Wake::Melody(tune) => {
+                melody = Some((tune, 0));
+                deadline = Some(Instant::now());
+            }
+            Wake::Timeout => {
+                let at = deadline.unwrap_or_else(Instant::now);
+                match melody.as_mut() {
+                    Some((tune, step)) if *step < tune.len() * 2 => {
+                        let note = tune[*step / 2];
+                        let gap = note.ms as u64 / 8;
+                        let (hz, ms) = if *step % 2 == 0 {
+                            (note.hz as u32, note.ms as u64 - gap)
+                        } else {
+                            (0, gap)
+                        };
+                        buzzer.tone(hz);
+                        // From the last deadline, so the tempo doesn't drift
+                        deadline = Some(at + Duration::from_millis(ms));
+                        *step += 1;
+                    }
+                    _ => {
+                        melody = None;
+                        buzzer.off();
+                        deadline = None;
+                    }
+                }
+            }
+        }
+    }
 }
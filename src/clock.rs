@@ -0,0 +1,294 @@
+//! An analog clock face
+//!
+//! The dial is [QUARTER_CIRCLE] mirrored into a whole circle, with hour,
+//! minute and second hands on top. The edges of the hands are antialiased,
+//! blended over the dial the same way alpha fonts are. Once the dial is up,
+//! each tick only redraws the areas the hands moved through.
+//!
+//! There's no RTC, so the time is the uptime plus an offset, set with
+//! [Clock::set].
+
+use embassy_time::Instant;
+use smart_leds::RGB8;
+
+use crate::{
+    color::{blend, rgb565_to_rgb8, rgb8_to_rgb565},
+    fmath::{cos, isqrt, sin},
+    image::{self, QUARTER_CIRCLE},
+    lcd::LcdPins,
+    spiflash::SpiFlash,
+};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Hands are worked out in 1/16ths of a pixel
+const SUB: i32 = 16;
+const CENTER: i32 = 120 * SUB;
+
+pub struct Clock {
+    /// Seconds from uptime to the time of day
+    offset: u64,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self { offset: 0 }
+    }
+
+    /// The time of day, in seconds since midnight
+    pub fn now(&self) -> u32 {
+        ((Instant::now().as_secs() + self.offset) % SECS_PER_DAY) as u32
+    }
+
+    /// Set the time of day, `h` is 0..24
+    pub fn set(&mut self, h: u32, m: u32, s: u32) {
+        let want = (h as u64 * 3600 + m as u64 * 60 + s as u64) % SECS_PER_DAY;
+        let up = Instant::now().as_secs() % SECS_PER_DAY;
+        self.offset = (want + SECS_PER_DAY - up) % SECS_PER_DAY;
+    }
+}
+
+/// A hand, drawn as a line with round ends from `tail` behind the center
+/// out to `len`, all in [SUB] pixels
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Hand {
+    /// 1/65536ths of a turn, clockwise from 12 o'clock
+    angle: u16,
+    len: i32,
+    tail: i32,
+    half_width: i32,
+    color: RGB8,
+}
+
+/// An area of the screen, end exclusive
+#[derive(Clone, Copy)]
+struct Rect {
+    xs: i32,
+    xe: i32,
+    ys: i32,
+    ye: i32,
+}
+
+impl Rect {
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            xs: self.xs.min(other.xs),
+            xe: self.xe.max(other.xe),
+            ys: self.ys.min(other.ys),
+            ye: self.ye.max(other.ye),
+        }
+    }
+}
+
+impl Hand {
+    /// Direction of the hand, scaled to +/-32767, with y growing downwards
+    fn unit(&self) -> (i32, i32) {
+        (sin(self.angle), -cos(self.angle))
+    }
+
+    /// How much of the pixel at `x`, `y` the hand covers, 0..=255
+    fn coverage(&self, x: i32, y: i32) -> u8 {
+        let (ux, uy) = self.unit();
+        let dx = x * SUB + SUB / 2 - CENTER;
+        let dy = y * SUB + SUB / 2 - CENTER;
+
+        let along = (dx * ux + dy * uy) >> 15;
+        let across = ((dx * uy - dy * ux) >> 15).abs();
+
+        // Distance to the line, or to the nearest end past either end
+        let dist = if along < -self.tail {
+            hypot(along + self.tail, across)
+        } else if along > self.len {
+            hypot(along - self.len, across)
+        } else {
+            across
+        };
+
+        // Fully covered half a pixel inside the edge, clear half a pixel out
+        let cover = (self.half_width + SUB / 2 - dist) * 255 / SUB;
+        cover.clamp(0, 255) as u8
+    }
+
+    /// The pixels the hand touches
+    fn bounds(&self) -> Rect {
+        let (ux, uy) = self.unit();
+        let tip = ((ux * self.len) >> 15, (uy * self.len) >> 15);
+        let tail = ((-ux * self.tail) >> 15, (-uy * self.tail) >> 15);
+        let pad = self.half_width + SUB;
+
+        let px = |v: i32| ((CENTER + v) / SUB).clamp(0, 239);
+        Rect {
+            xs: px(tip.0.min(tail.0) - pad),
+            xe: px(tip.0.max(tail.0) + pad) + 1,
+            ys: px(tip.1.min(tail.1) - pad),
+            ye: px(tip.1.max(tail.1) + pad) + 1,
+        }
+    }
+}
+
+fn hypot(a: i32, b: i32) -> i32 {
+    isqrt((a * a + b * b) as u32) as i32
+}
+
+const HOUR: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+const MINUTE: RGB8 = HOUR;
+const SECOND: RGB8 = RGB8 {
+    r: 255,
+    g: 64,
+    b: 32,
+};
+
+/// The hands at `secs` past midnight, bottom to top
+fn hands(secs: u32) -> [Hand; 4] {
+    let turn = |n: u32, per: u32| ((n % per) as u64 * 65536 / per as u64) as u16;
+    [
+        Hand {
+            angle: turn(secs, 12 * 3600),
+            len: 55 * SUB,
+            tail: 10 * SUB,
+            half_width: 4 * SUB,
+            color: HOUR,
+        },
+        Hand {
+            angle: turn(secs, 3600),
+            len: 85 * SUB,
+            tail: 12 * SUB,
+            half_width: 5 * SUB / 2,
+            color: MINUTE,
+        },
+        Hand {
+            angle: turn(secs, 60),
+            len: 95 * SUB,
+            tail: 20 * SUB,
+            half_width: SUB,
+            color: SECOND,
+        },
+        // The cap over the middle
+        Hand {
+            angle: 0,
+            len: 0,
+            tail: 0,
+            half_width: 5 * SUB,
+            color: SECOND,
+        },
+    ]
+}
+
+/// What's on the screen, so only the hands that moved are redrawn
+pub struct Face {
+    shown: Option<[Hand; 4]>,
+}
+
+impl Face {
+    pub fn new() -> Self {
+        Self { shown: None }
+    }
+
+    /// Draw the dial and the hands
+    pub async fn draw(
+        &mut self,
+        lcd: &mut LcdPins,
+        mut spif: Option<&mut SpiFlash>,
+        secs: u32,
+    ) -> Result<(), ()> {
+        self.shown = None;
+        image::blit_quad(lcd, spif.as_deref_mut(), &QUARTER_CIRCLE).await?;
+
+        let new = hands(secs);
+        for hand in new.iter() {
+            render(lcd, spif.as_deref_mut(), hand.bounds(), &new).await?;
+        }
+        self.shown = Some(new);
+        Ok(())
+    }
+
+    /// Move the hands, redrawing the dial only where they were and are now
+    pub async fn update(
+        &mut self,
+        lcd: &mut LcdPins,
+        mut spif: Option<&mut SpiFlash>,
+        secs: u32,
+    ) -> Result<(), ()> {
+        let Some(old) = self.shown.take() else {
+            return self.draw(lcd, spif, secs).await;
+        };
+
+        let new = hands(secs);
+        for (old, new_hand) in old.iter().zip(new.iter()) {
+            if old != new_hand {
+                render(
+                    lcd,
+                    spif.as_deref_mut(),
+                    old.bounds().union(&new_hand.bounds()),
+                    &new,
+                )
+                .await?;
+            }
+        }
+        self.shown = Some(new);
+        Ok(())
+    }
+}
+
+/// Draw the dial and `hands` over `rect`
+async fn render(
+    lcd: &mut LcdPins,
+    mut spif: Option<&mut SpiFlash>,
+    rect: Rect,
+    hands: &[Hand],
+) -> Result<(), ()> {
+    if rect.xs >= rect.xe || rect.ys >= rect.ye {
+        return Ok(());
+    }
+
+    let mut line = [0u16; 240];
+    let mut bytes = [0u8; 240 * 2];
+    let mut scratch = [0u8; 256 * 2];
+    let line = &mut line[rect.xs as usize..rect.xe as usize];
+    let bytes = &mut bytes[..line.len() * 2];
+
+    lcd.start_write(rect.xs as u8, rect.xe as u8, rect.ys as u8, rect.ye as u8)
+        .await
+        .map_err(drop)?;
+
+    let mut res = Ok(());
+    for y in rect.ys..rect.ye {
+        res = image::quad_row(
+            spif.as_deref_mut(),
+            &QUARTER_CIRCLE,
+            y as usize,
+            rect.xs as usize,
+            line,
+            &mut scratch,
+        )
+        .await;
+        if res.is_err() {
+            break;
+        }
+
+        for (x, px) in (rect.xs..).zip(line.iter_mut()) {
+            for hand in hands {
+                *px = match hand.coverage(x, y) {
+                    0 => *px,
+                    255 => rgb8_to_rgb565(hand.color),
+                    a => blend(hand.color, rgb565_to_rgb8(*px), a),
+                };
+            }
+        }
+
+        for (px, dst) in line.iter().zip(bytes.chunks_exact_mut(2)) {
+            dst.copy_from_slice(&px.to_be_bytes());
+        }
+        if lcd.write_pixels(bytes).await.is_err() {
+            res = Err(());
+            break;
+        }
+    }
+
+    lcd.end_write();
+    res
+}
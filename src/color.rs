@@ -0,0 +1,57 @@
+//! Conversions between the RGB8 colors used around the firmware and the
+//! RGB565 values the display wants.
+
+use smart_leds::RGB8;
+
+pub fn rgb565_to_rgb8(color: u16) -> RGB8 {
+    let r = ((color >> 8) & 0b11111000) as u8;
+    let g = ((color >> 3) & 0b11111100) as u8;
+    let b = ((color << 3) & 0b11111000) as u8;
+    (r, g, b).into()
+}
+
+pub fn rgb8_to_rgb565(rgb: RGB8) -> u16 {
+    let r = (rgb.r as u16 & 0b11111000) << 8;
+    let g = (rgb.g as u16 & 0b11111100) << 3;
+    let b = rgb.b as u16 >> 3;
+    r | g | b
+}
+
+/// Mix `fg` over `bg` with an 8-bit `alpha`, returning RGB565
+pub fn blend(fg: RGB8, bg: RGB8, alpha: u8) -> u16 {
+    // Here "plus" is how much we take from the foreground color,
+    // and "minus" is how much we take from the background color.
+    let plus = alpha as u16;
+    let minus = 255 - plus;
+
+    // Apply alpha to each channel, extending it to 16 bits for each
+    // channel (8b x 8b = 16b), then scale back down by 255 so that fully
+    // transparent or opaque pixels come out exactly as `bg` or `fg`. Trimmed
+    // glyphs rely on this to match the plain background around them.
+    let mix = |fg: u8, bg: u8| {
+        let v = fg as u16 * plus + bg as u16 * minus;
+        // v / 255, without a divide
+        ((v + 1 + (v >> 8)) >> 8) as u8
+    };
+
+    rgb8_to_rgb565(RGB8 {
+        r: mix(fg.r, bg.r),
+        g: mix(fg.g, bg.g),
+        b: mix(fg.b, bg.b),
+    })
+}
+
+/// Input a value 0 to 255 to get a color value
+/// The colours are a transition r - g - b - back to r.
+pub fn wheel(mut wheel_pos: u8) -> RGB8 {
+    wheel_pos = 255 - wheel_pos;
+    if wheel_pos < 85 {
+        return (255 - wheel_pos * 3, 0, wheel_pos * 3).into();
+    }
+    if wheel_pos < 170 {
+        wheel_pos -= 85;
+        return (0, wheel_pos * 3, 255 - wheel_pos * 3).into();
+    }
+    wheel_pos -= 170;
+    (wheel_pos * 3, 255 - wheel_pos * 3, 0).into()
+}
@@ -1,14 +1,20 @@
 use core::fmt::Write;
 
 use embassy_rp::{bind_interrupts, adc::{self, Adc, Channel}};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
 use embassy_time::{Duration, Timer};
 
-use crate::forth::OUTPIPE;
+use crate::forth::{OUTPIPE, OUTPIPE_LOCK};
 
 bind_interrupts!(pub struct Irqs {
     ADC_IRQ_FIFO => adc::InterruptHandler;
 });
 
+/// The highest reading, the ADC is 12 bits
+pub const DIAL_MAX: u16 = 4095;
+
+/// Signalled with the new level whenever the dial moves
+pub static DIAL: Signal<ThreadModeRawMutex, u16> = Signal::new();
 
 #[embassy_executor::task]
 pub async fn dial(mut adc: Adc<'static, adc::Async>, mut pin: Channel<'static>) {
@@ -18,9 +24,13 @@ pub async fn dial(mut adc: Adc<'static, adc::Async>, mut pin: Channel<'static>)
         let new_level = adc.read(&mut pin).await.unwrap();
         if level.abs_diff(new_level) >= 16 {
             write!(&mut strbuf, "\r\n{new_level}\r\n").ok();
-            OUTPIPE.write_all(strbuf.as_bytes()).await;
+            {
+                let _out = OUTPIPE_LOCK.lock().await;
+                OUTPIPE.write_all(strbuf.as_bytes()).await;
+            }
             strbuf.clear();
             level = new_level;
+            DIAL.signal(level);
         }
         Timer::after(Duration::from_millis(50)).await;
     }
@@ -0,0 +1,181 @@
+//! Bitmap fonts
+//!
+//! Every font is a table of glyphs, found by looking a character up in its
+//! [CharRange]s. Characters a font doesn't have get a replacement glyph, an
+//! outlined box, so control characters or stray bytes never index past the
+//! table. Each glyph is trimmed to the pixels it draws, and placed inside a box
+//! `advance` pixels wide and one line high. Glyphs are stored row by row,
+//! either as 1 bit per pixel (MSB first, each row padded to a whole byte) or
+//! as an 8-bit alpha value per pixel.
+
+use smart_leds::RGB8;
+
+use crate::color::{blend, rgb8_to_rgb565};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FontKind {
+    /// 1 bit per pixel, MSB first
+    Bit,
+    /// 8-bit alpha per pixel, blended between foreground and background
+    Alpha,
+}
+
+/// Where one character lives in [Font::data], and where it's drawn
+pub struct Glyph {
+    /// Byte offset of the first row
+    pub offset: u32,
+    /// Position of the bitmap within the character's box
+    pub left: u8,
+    pub top: u8,
+    /// Size of the bitmap, zero for characters that draw nothing
+    pub width: u8,
+    pub height: u8,
+    /// Width of the character's box, the distance to the next character
+    pub advance: u8,
+}
+
+/// The characters `first..=last` are the glyphs starting at `glyph`
+pub struct CharRange {
+    pub first: u32,
+    pub last: u32,
+    pub glyph: u16,
+}
+
+pub struct Font {
+    pub name: &'static str,
+    pub kind: FontKind,
+    /// Every glyph has the same advance
+    pub monospace: bool,
+    /// The widest advance of any glyph
+    pub char_width_px: usize,
+    /// The height of a line
+    pub char_height_px: usize,
+    pub chars: &'static [CharRange],
+    /// Index of the glyph for characters not in `chars`
+    pub replacement: u16,
+    pub glyphs: &'static [Glyph],
+    pub data: &'static [u8],
+}
+
+// The fonts themselves are converted from `assets/` by `build.rs`, which
+// also checks their metrics, see `build/assets.rs`.
+include!(concat!(env!("OUT_DIR"), "/fonts.rs"));
+
+impl Font {
+    /// Size of a buffer that can hold any one character
+    pub fn char_buf_size(&self) -> usize {
+        self.char_width_px * self.char_height_px * core::mem::size_of::<u16>()
+    }
+
+    fn glyph_idx(&self, ch: char) -> Option<usize> {
+        let ch = ch as u32;
+        self.chars
+            .iter()
+            .find(|r| (r.first..=r.last).contains(&ch))
+            .map(|r| r.glyph as usize + (ch - r.first) as usize)
+    }
+
+    /// The glyph for `ch`, or the replacement glyph. Only `None` if the
+    /// font's tables are broken.
+    pub fn glyph(&self, ch: char) -> Option<&Glyph> {
+        let idx = self.glyph_idx(ch).unwrap_or(self.replacement as usize);
+        self.glyphs.get(idx)
+    }
+
+    /// Width in pixels of `ch`
+    pub fn advance(&self, ch: char) -> usize {
+        self.glyph(ch).map(|g| g.advance as usize).unwrap_or(0)
+    }
+
+    /// Render `ch` into the start of `buf` as big-endian RGB565, one
+    /// `advance` wide row at a time. Returns the advance.
+    pub fn render(&self, buf: &mut [u8], ch: char, fg: RGB8, bg: RGB8) -> Result<usize, ()> {
+        let glyph = self.glyph(ch).ok_or(())?;
+        let advance = glyph.advance as usize;
+        let buf = buf.get_mut(..advance * self.char_height_px * 2).ok_or(())?;
+
+        let fits_x = glyph.left as usize + glyph.width as usize <= advance;
+        let fits_y = glyph.top as usize + glyph.height as usize <= self.char_height_px;
+        if !(fits_x && fits_y) {
+            return Err(());
+        }
+
+        let bg_565 = rgb8_to_rgb565(bg).to_be_bytes();
+        for px in buf.chunks_exact_mut(2) {
+            px.copy_from_slice(&bg_565);
+        }
+        if glyph.width == 0 || glyph.height == 0 {
+            return Ok(advance);
+        }
+
+        // Just the part of each row covered by the bitmap
+        let dst_rows = buf
+            .chunks_exact_mut(advance * 2)
+            .skip(glyph.top as usize)
+            .map(|row| &mut row[glyph.left as usize * 2..][..glyph.width as usize * 2]);
+
+        match self.kind {
+            FontKind::Bit => self.glyph_bit_to_be_bytes(dst_rows, glyph, fg)?,
+            FontKind::Alpha => self.glyph_alpha_to_be_bytes(dst_rows, glyph, fg, bg)?,
+        }
+
+        Ok(advance)
+    }
+
+    fn glyph_alpha_to_be_bytes<'a>(
+        &self,
+        dst_rows: impl Iterator<Item = &'a mut [u8]>,
+        glyph: &Glyph,
+        set_val: RGB8,
+        clr_val: RGB8,
+    ) -> Result<(), ()> {
+        let width = glyph.width as usize;
+        let height = glyph.height as usize;
+        let src = self
+            .data
+            .get(glyph.offset as usize..)
+            .and_then(|d| d.get(..width * height))
+            .ok_or(())?;
+
+        dst_rows
+            .zip(src.chunks_exact(width))
+            .flat_map(|(dst_row, src_row)| dst_row.chunks_exact_mut(2).zip(src_row))
+            .for_each(|(dst_2b, alpha_1b)| {
+                let px = blend(set_val, clr_val, *alpha_1b);
+                dst_2b.copy_from_slice(&px.to_be_bytes());
+            });
+
+        Ok(())
+    }
+
+    fn glyph_bit_to_be_bytes<'a>(
+        &self,
+        dst_rows: impl Iterator<Item = &'a mut [u8]>,
+        glyph: &Glyph,
+        set_val: RGB8,
+    ) -> Result<(), ()> {
+        let set_val = rgb8_to_rgb565(set_val).to_be_bytes();
+
+        let width = glyph.width as usize;
+        let height = glyph.height as usize;
+        // Each row is padded out to a whole byte
+        let row_bytes = (width + 7) / 8;
+        let src = self
+            .data
+            .get(glyph.offset as usize..)
+            .and_then(|d| d.get(..row_bytes * height))
+            .ok_or(())?;
+
+        dst_rows
+            .zip(src.chunks_exact(row_bytes))
+            .for_each(|(dst_row, src_row)| {
+                for (x, dst_2b) in dst_row.chunks_exact_mut(2).enumerate() {
+                    if src_row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                        dst_2b.copy_from_slice(&set_val);
+                    }
+                }
+            });
+
+        Ok(())
+    }
+}
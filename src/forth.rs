@@ -1,11 +1,20 @@
 use core::{
-    alloc::Layout, cell::UnsafeCell, fmt::Write, future::Future, mem::MaybeUninit, ptr::NonNull,
+    alloc::Layout,
+    cell::{Cell, UnsafeCell},
+    fmt::Write,
+    future::Future,
+    mem::MaybeUninit,
+    ptr::NonNull,
     unreachable,
 };
 
+use embassy_futures::select::{select, select4, Either, Either4};
 use embassy_rp::rom_data;
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pipe::Pipe};
-use embassy_time::{Duration, Timer};
+use embassy_sync::{
+    blocking_mutex::{raw::ThreadModeRawMutex, Mutex},
+    pipe::Pipe,
+};
+use embassy_time::{with_timeout, Duration, Instant, Timer};
 use forth3::{
     async_builtin, builtin,
     dictionary::{
@@ -21,47 +30,64 @@ use forth3::{
 use smart_leds::{colors, RGB8};
 
 use crate::{
-    gc9a01a::registers::{InitOp, GC9A01A_CASET, GC9A01A_PASET, GC9A01A_RAMWR, INIT_SEQ},
-    lcd::LcdBuf,
-    leds::Leds,
-    ws2812::wheel,
+    buttons::{Button, ButtonEvent, BUTTON_EVENTS},
+    buzzer::{self, BuzzerCmd, Samples, BUZZER_CMDS},
+    clock::{Clock, Face},
+    color::{rgb565_to_rgb8, wheel},
+    fonts::{Font, FontKind, FONTS},
+    image::{self, Image, ImageSource, Mirror, ASSETS},
+    lcd::{LcdBuf, LcdState, Orientation},
+    leds::{Effect, LedCmd, PwmSettings, LED_CMDS, NUM_LEDS},
+    power::{Power, BUTTON_ACTIVITY},
+    shadow,
+    smartled::{self, Effect as SmartEffect, SmartLedCmd, StripCmd, SMARTLED_CMDS},
+    sprite::{self, Background, Mask, MaskKind, Scene, Source, Sprite},
+    text::{draw_str, text_width},
+    widgets::{self, Alert, Gauge, Input, List, Menu, Modal, Progress, Readout, Response, Theme},
     LcdPins, spiflash::SpiFlash,
 };
 
-const FONT: Font = Font {
-    font: include_bytes!("../ProFont24Point.raw"),
-    font_width_chars: 32,
-    font_height_chars: 6,
-    char_width_px: 16,
-    char_height_px: 29,
-};
-
-const FONT2: Font = Font {
-    font: include_bytes!("../assets/source-code-pro-ascii.aff"),
-    font_width_chars: 32,
-    font_height_chars: 3,
-    char_width_px: 14,
-    char_height_px: 31,
-};
-
 pub struct RobertCtx {
-    pub has_init: bool,
     pub lcd: LcdPins,
     pub lcd_buf: LcdBuf,
-    pub leds: Leds,
-    pub spif: SpiFlash,
+    /// `None` when the add-on port has something else on it
+    pub spif: Option<SpiFlash>,
+    pub power: Power,
+    pub scene: Scene,
+    /// Colors used by the widgets
+    pub theme: Theme,
+    pub clock: Clock,
+    /// The strip the smart-LED words act on
+    pub strip: u8,
+    pub str_scratch: [u8; 64],
+    /// Index into FONTS used for all text drawing
+    pub font_idx: usize,
 }
 
 impl RobertCtx {
-    pub fn new(lcd: LcdPins, leds: Leds, spif: SpiFlash) -> Self {
+    pub fn new(lcd: LcdPins, spif: Option<SpiFlash>) -> Self {
         Self {
-            has_init: false,
             lcd,
             lcd_buf: LcdBuf::new(),
-            leds,
             spif,
+            power: Power::new(),
+            scene: Scene::new(sprite::take_ram().unwrap()),
+            theme: Theme::new(),
+            clock: Clock::new(),
+            strip: 0,
+            str_scratch: [0; 64],
+            font_idx: 0,
         }
     }
+
+    pub fn font(&self) -> &'static Font {
+        &FONTS[self.font_idx]
+    }
+}
+
+/// The flash, for words that need it
+fn flash(spif: &mut Option<SpiFlash>) -> Result<&mut SpiFlash, forth3::Error> {
+    spif.as_mut().ok_or(forth3::Error::BadLiteral)
 }
 
 pub struct RobertAlloc {}
@@ -111,7 +137,7 @@ async fn init(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
 }
 
 async fn get_spi_id(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
-    let vals = forth.host_ctxt.spif.get_id().await;
+    let vals = flash(&mut forth.host_ctxt.spif)?.get_id().await;
     writeln!(&mut forth.output, "SPI said: {:02X?}\r", &vals)?;
 
     Ok(())
@@ -140,6 +166,7 @@ async fn print_line(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
     let idx = unsafe { forth.data_stack.try_pop()?.data } as u8;
 
     let col = linecolor(idx);
+    let font = forth.host_ctxt.font();
     let lcd = &mut forth.host_ctxt.lcd;
     let lcd_buf = &mut forth.host_ctxt.lcd_buf;
     let xrange = lcd_buf.get_x_range(idx);
@@ -162,52 +189,184 @@ async fn print_line(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
         return Ok(());
     }
 
-    let len = txt.len().min(txt_buf.len());
+    // The line holds a number of characters, not bytes
+    let len = txt
+        .char_indices()
+        .nth(txt_buf.len())
+        .map(|(i, _)| i)
+        .unwrap_or(txt.len());
 
-    let txt = &txt[..len];
-
-    let mut buf = [0u8; 1024];
-    let buf = &mut buf[..FONT2.char_buf_size()];
+    let txt = &txt.as_bytes()[..len];
 
     // Figure out the starting X position centered
-    let txt_width = txt.len() * FONT2.char_width_px;
+    let txt_width = text_width(font, txt);
     let ttl_width = (xe - xs) as usize;
-    let delta_w = ttl_width - txt_width;
+    let delta_w = ttl_width.saturating_sub(txt_width);
     let half_delta_w = (delta_w / 2) as u8;
     let xs = xs + half_delta_w;
-    let mut x_pos = xs;
-
-    for ch in txt.as_bytes() {
-        let idx = ch - b' ';
-        let ch_x = idx % 32;
-        let ch_y = idx / 32;
-
-        let rgb = {
-            let r = ((color >> 8) & 0b11111000) as u8;
-            let g = ((color >> 2) & 0b11111100) as u8;
-            let b = ((color << 3) & 0b11111000) as u8;
-            (r, g, b)
-        };
-
-        FONT2
-            .font_alpha_to_be_bytes(buf, ch_x.into(), ch_y.into(), colors::WHITE, rgb.into())
-            .unwrap();
 
-        lcd.draw(
-            x_pos,
-            x_pos + FONT2.char_width_px as u8,
-            ys,
-            ys + FONT2.char_height_px as u8,
-            buf,
-        )
+    draw_str(lcd, font, xs, ys, txt, colors::WHITE, rgb565_to_rgb8(color))
         .await
         .ok();
 
-        x_pos += FONT2.char_width_px as u8;
+    forth.output.clear();
+
+    Ok(())
+}
+
+/// Read a forth `addr len` pair as a byte string
+///
+/// # Safety
+///
+/// Same as `b@`, the address is trusted to point at `len` readable bytes.
+unsafe fn forth_str<'a>(addr: i32, len: i32) -> Result<&'a [u8], forth3::Error> {
+    if addr == 0 || len < 0 {
+        return Err(forth3::Error::BadLiteral);
     }
+    Ok(core::slice::from_raw_parts(addr as usize as *const u8, len as usize))
+}
 
-    forth.output.clear();
+// x y addr len fg bg draw-text
+//
+// Colors are RGB565, like `rect`
+async fn draw_text(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let bg = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let fg = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let y = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let x = unsafe { forth.data_stack.try_pop()?.data } as u8;
+
+    let txt = unsafe { forth_str(addr, len)? };
+    let font = forth.host_ctxt.font();
+    let lcd = &mut forth.host_ctxt.lcd;
+    draw_str(lcd, font, x, y, txt, rgb565_to_rgb8(fg), rgb565_to_rgb8(bg))
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// y addr len fg bg center-text
+//
+// Like `draw-text`, centered left to right on the screen
+async fn center_text(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let bg = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let fg = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let y = unsafe { forth.data_stack.try_pop()?.data } as u8;
+
+    let txt = unsafe { forth_str(addr, len)? };
+    let font = forth.host_ctxt.font();
+    let x = (240usize.saturating_sub(text_width(font, txt)) / 2) as u8;
+    let lcd = &mut forth.host_ctxt.lcd;
+    draw_str(lcd, font, x, y, txt, rgb565_to_rgb8(fg), rgb565_to_rgb8(bg))
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// idx font-select
+fn font_select(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let idx = unsafe { forth.data_stack.try_pop()?.data };
+    let idx = usize::try_from(idx).map_err(|_| forth3::Error::BadLiteral)?;
+    if idx >= FONTS.len() {
+        return Err(forth3::Error::BadLiteral);
+    }
+    forth.host_ctxt.font_idx = idx;
+    Ok(())
+}
+
+// x y font
+//
+// Draws "butts" in ProFont. From before `font-select`, kept for old scripts.
+async fn font(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    font_demo(forth, "profont").await
+}
+
+// x y font2
+//
+// Like `font`, in Source Code Pro
+async fn font2(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    font_demo(forth, "source-code-pro").await
+}
+
+async fn font_demo(forth: &mut Forth<RobertCtx>, name: &str) -> Result<(), forth3::Error> {
+    let y = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let x = unsafe { forth.data_stack.try_pop()?.data } as u8;
+
+    let font = FONTS
+        .iter()
+        .find(|f| f.name == name)
+        .ok_or(forth3::Error::BadLiteral)?;
+    let lcd = &mut forth.host_ctxt.lcd;
+    draw_str(lcd, font, x, y, b"butts", colors::WHITE, colors::BLACK)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// addr len text-width ( -- w )
+//
+// Width in pixels of the string in the selected font
+fn forth_text_width(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let txt = unsafe { forth_str(addr, len)? };
+    let width = text_width(forth.host_ctxt.font(), txt);
+    forth.data_stack.push(Word::data(width as i32))?;
+    Ok(())
+}
+
+// font-metrics ( -- w h )
+//
+// `w` is the widest character, see `text-width` for the real width of a string
+fn font_metrics(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let font = forth.host_ctxt.font();
+    forth.data_stack.push(Word::data(font.char_width_px as i32))?;
+    forth.data_stack.push(Word::data(font.char_height_px as i32))?;
+    Ok(())
+}
+
+// List the registered fonts
+fn list_fonts(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    for (idx, font) in FONTS.iter().enumerate() {
+        let kind = match font.kind {
+            FontKind::Bit => "1-bit",
+            FontKind::Alpha => "alpha",
+        };
+        let spacing = if font.monospace { "mono" } else { "prop" };
+        let sel = if idx == forth.host_ctxt.font_idx { '*' } else { ' ' };
+        writeln!(
+            &mut forth.output,
+            "{sel}{idx} {} {}x{} {kind} {spacing}\r",
+            font.name, font.char_width_px, font.char_height_px,
+        )?;
+    }
+    Ok(())
+}
+
+// s" text" ( -- addr len )
+//
+// Copies the literal into a scratch buffer, which is reused by the next
+// `s"`. Only works when interpreting, not inside a `:` definition.
+fn s_quote(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    forth
+        .input
+        .advance_str()
+        .map_err(|_| forth3::Error::LQuoteMissingRQuote)?;
+    let lit = forth
+        .input
+        .cur_str_literal()
+        .ok_or(forth3::Error::LQuoteMissingRQuote)?;
+
+    let scratch = &mut forth.host_ctxt.str_scratch;
+    if lit.len() > scratch.len() {
+        return Err(forth3::Error::LiteralStringTooLong);
+    }
+    scratch[..lit.len()].copy_from_slice(lit.as_bytes());
 
+    let addr = scratch.as_ptr() as usize as i32;
+    let len = lit.len() as i32;
+    forth.data_stack.push(Word::data(addr))?;
+    forth.data_stack.push(Word::data(len))?;
     Ok(())
 }
 
@@ -230,56 +389,221 @@ fn set_backlight(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
     let data = data.max(0).min(u16::MAX.into());
     let data = data as u16;
 
-    let mut config = embassy_rp::pwm::Config::default();
-    config.top = u16::MAX;
-    config.compare_b = data;
-    config.enable = true;
+    forth.host_ctxt.lcd.backlight.set_duty(data);
+
+    Ok(())
+}
+
+// level brightness
+fn brightness(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let level = unsafe { forth.data_stack.try_pop()?.data };
+    let level = level.max(0).min(u8::MAX.into()) as u8;
+
+    forth.host_ctxt.lcd.backlight.set_level(level);
 
-    forth.host_ctxt.lcd.backlight.set_config(&config);
+    Ok(())
+}
+
+// level ms fade
+async fn fade(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ms = unsafe { forth.data_stack.try_pop()?.data };
+    let level = unsafe { forth.data_stack.try_pop()?.data };
+    let level = level.max(0).min(u8::MAX.into()) as u8;
+    let ms = ms.max(0) as u64;
+
+    forth
+        .host_ctxt
+        .lcd
+        .backlight
+        .fade_to(level, Duration::from_millis(ms))
+        .await;
 
     Ok(())
 }
 
-// idx amt set_led
+// dim_secs sleep_secs lcd-timeout
+//
+// A timeout of 0 disables that step
+fn lcd_timeout(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let sleep_secs = unsafe { forth.data_stack.try_pop()?.data };
+    let dim_secs = unsafe { forth.data_stack.try_pop()?.data };
+
+    let to_dur = |secs: i32| (secs > 0).then(|| Duration::from_secs(secs as u64));
+
+    let power = &mut forth.host_ctxt.power;
+    power.dim_after = to_dur(dim_secs);
+    power.sleep_after = to_dur(sleep_secs);
+
+    Ok(())
+}
+
+async fn lcd_sleep(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ctx = &mut forth.host_ctxt;
+    ctx.power.sleep(&mut ctx.lcd).await;
+    Ok(())
+}
+
+async fn lcd_wake(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ctx = &mut forth.host_ctxt;
+    ctx.power.wake(&mut ctx.lcd).await;
+    Ok(())
+}
+
+// idx level set_led
+//
+// LED brightness is a perceptual level, 0..=255
 fn set_led(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
     let amt = unsafe { forth.data_stack.try_pop()?.data };
-    let amt = amt.max(0).min(u16::MAX.into());
-    let amt = amt as u16;
+    let amt = amt.clamp(0, u8::MAX.into()) as u8;
 
-    let idx = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let idx = unsafe { forth.data_stack.try_pop()?.data };
 
-    forth
-        .host_ctxt
-        .leds
-        .set_led(idx, amt)
+    led_effect(idx, Effect::Set(amt))
+}
+
+/// Hand an effect to the LED task
+fn led_effect(idx: i32, effect: Effect) -> Result<(), forth3::Error> {
+    let idx = u8::try_from(idx).map_err(|_| forth3::Error::BadLiteral)?;
+    if idx as usize >= NUM_LEDS {
+        return Err(forth3::Error::BadLiteral);
+    }
+    LED_CMDS
+        .try_send(LedCmd::Play { idx, effect })
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+/// Pop the `idx amt time` taken by all the timed LED words
+fn led_args(forth: &mut Forth<RobertCtx>) -> Result<(i32, u8, u32), forth3::Error> {
+    let time = unsafe { forth.data_stack.try_pop()?.data };
+    let amt = unsafe { forth.data_stack.try_pop()?.data };
+    let idx = unsafe { forth.data_stack.try_pop()?.data };
+    let amt = amt.clamp(0, u8::MAX.into()) as u8;
+    let time = u32::try_from(time).map_err(|_| forth3::Error::BadLiteral)?;
+    Ok((idx, amt, time))
+}
+
+// level led-master
+//
+// Scale the brightness of all the LEDs, 0..=255
+fn led_master(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let level = unsafe { forth.data_stack.try_pop()?.data };
+    let level = level.clamp(0, u8::MAX.into()) as u8;
+    LED_CMDS
+        .try_send(LedCmd::Master(level))
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// idx hz top phase-correct? invert? led-pwm
+//
+// Set up how one LED's PWM runs. `top` is the number of brightness steps
+// minus one, and `invert?` is for LEDs wired active-low.
+fn led_pwm(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let invert = unsafe { forth.data_stack.try_pop()?.data } != 0;
+    let phase_correct = unsafe { forth.data_stack.try_pop()?.data } != 0;
+    let top = unsafe { forth.data_stack.try_pop()?.data };
+    let freq = unsafe { forth.data_stack.try_pop()?.data };
+    let idx = unsafe { forth.data_stack.try_pop()?.data };
+
+    let settings = PwmSettings {
+        freq: u32::try_from(freq).map_err(|_| forth3::Error::BadLiteral)?,
+        top: u16::try_from(top).map_err(|_| forth3::Error::BadLiteral)?,
+        phase_correct,
+        invert,
+    };
+    let idx = u8::try_from(idx).map_err(|_| forth3::Error::BadLiteral)?;
+    if idx as usize >= NUM_LEDS {
+        return Err(forth3::Error::BadLiteral);
+    }
+    LED_CMDS
+        .try_send(LedCmd::Pwm { idx, settings })
         .map_err(|_| forth3::Error::BadLiteral)
 }
 
+// idx target ms led-fade
+fn led_fade(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (idx, target, ms) = led_args(forth)?;
+    led_effect(idx, Effect::Fade { target, ms })
+}
+
+// idx peak period led-breathe
+fn led_breathe(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (idx, peak, period) = led_args(forth)?;
+    led_effect(idx, Effect::Breathe { peak, period })
+}
+
+// idx peak period led-blink
+fn led_blink(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (idx, peak, period) = led_args(forth)?;
+    led_effect(idx, Effect::Blink { peak, period })
+}
+
+// idx peak period led-heartbeat
+fn led_heartbeat(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (idx, peak, period) = led_args(forth)?;
+    led_effect(idx, Effect::Heartbeat { peak, period })
+}
+
+// idx peak ms led-pulse
+//
+// Up to `peak` and back to where the LED was, once
+fn led_pulse(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (idx, peak, ms) = led_args(forth)?;
+    led_effect(idx, Effect::Pulse { peak, ms })
+}
+
 async fn init_disp(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
-    if forth.host_ctxt.has_init {
+    let lcd = &mut forth.host_ctxt.lcd;
+    if lcd.state != LcdState::Uninit {
         return Ok(());
     }
-    forth.host_ctxt.has_init = true;
 
+    lcd.hard_reset().await;
+    lcd.init().await.ok();
+
+    Ok(())
+}
+
+// Reset and reinitialize a glitched display, then clear it
+async fn lcd_reinit(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
     let lcd = &mut forth.host_ctxt.lcd;
 
-    for c in INIT_SEQ {
-        match c {
-            InitOp::Cmd(c) => {
-                // command
-                lcd.command(&[c.cmd]).await.ok();
-                Timer::after(Duration::from_micros(10)).await;
+    if lcd.reinit().await.is_err() {
+        writeln!(&mut forth.output, "LCD reinit failed\r")?;
+        return Ok(());
+    }
+    rect_inner(lcd, 0, 240, 0, 240, 0x0000).await.ok();
 
-                // data
-                lcd.data(c.data).await.ok();
-                Timer::after(Duration::from_micros(10)).await;
-            }
-            InitOp::Delay(ms) => {
-                Timer::after(Duration::from_millis(ms.into())).await;
-            }
-        }
+    Ok(())
+}
+
+// rot rotate
+async fn rotate(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let rot = unsafe { forth.data_stack.try_pop()?.data };
+    if !(0..=3).contains(&rot) {
+        return Err(forth3::Error::BadLiteral);
     }
 
+    let lcd = &mut forth.host_ctxt.lcd;
+    let orientation = Orientation {
+        rotation: rot as u8,
+        ..lcd.orientation
+    };
+    lcd.set_orientation(orientation).await.ok();
+    Ok(())
+}
+
+// flip_x flip_y mirror
+async fn mirror(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let flip_y = unsafe { forth.data_stack.try_pop()?.data } != 0;
+    let flip_x = unsafe { forth.data_stack.try_pop()?.data } != 0;
+
+    let lcd = &mut forth.host_ctxt.lcd;
+    let orientation = Orientation {
+        mirror_x: flip_x,
+        mirror_y: flip_y,
+        ..lcd.orientation
+    };
+    lcd.set_orientation(orientation).await.ok();
     Ok(())
 }
 
@@ -295,11 +619,7 @@ async fn rect_inner(
         return Ok(());
     }
 
-    lcd.command(&[GC9A01A_CASET]).await.ok();
-    lcd.data(&[0x00, xs, 0x00, xe - 1]).await.ok();
-    lcd.command(&[GC9A01A_PASET]).await.ok();
-    lcd.data(&[0x00, ys, 0x00, ye - 1]).await.ok();
-    lcd.command(&[GC9A01A_RAMWR]).await.ok();
+    lcd.start_write(xs, xe, ys, ye).await.ok();
 
     let mut buf = [0u8; 4096];
     let color = color.to_be_bytes();
@@ -309,20 +629,49 @@ async fn rect_inner(
 
     let mut remaining = (ye as usize - ys as usize) * (xe as usize - xs as usize) * 2;
 
-    lcd.cs.set_low();
-    lcd.dc.set_high();
-
     while remaining != 0 {
         let take = remaining.min(4096);
         remaining -= take;
-        lcd.spi.write(&buf[..take]).await.ok();
+        lcd.write_pixels(&buf[..take]).await.ok();
     }
 
-    lcd.cs.set_high();
+    lcd.end_write();
 
     Ok(())
 }
 
+/// The start of the PPM sent by `screenshot`, for a 240x240 RGB image
+const PPM_HEADER: &[u8] = b"P6\n240 240\n255\n";
+
+// screenshot
+//
+// Send the screen to the host as a binary PPM, from the shadow copy.
+// It's framed with lines the host can look for:
+//
+//   "\r\nSCREENSHOT <len>\r\n" <len bytes of PPM> "\r\nEND SCREENSHOT\r\n"
+async fn screenshot(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let shadow = &forth.host_ctxt.lcd.shadow;
+    let len = PPM_HEADER.len() + shadow::WIDTH * shadow::HEIGHT * 3;
+
+    let mut header = heapless::String::<32>::new();
+    write!(&mut header, "\r\nSCREENSHOT {len}\r\n").map_err(|_| forth3::Error::BadLiteral)?;
+    let _out = OUTPIPE_LOCK.lock().await;
+    OUTPIPE.write_all(header.as_bytes()).await;
+    OUTPIPE.write_all(PPM_HEADER).await;
+
+    let mut rgb = [0u8; shadow::WIDTH * 3];
+    for y in 0..shadow::HEIGHT {
+        for (src, dst) in shadow.row(y).chunks_exact(2).zip(rgb.chunks_exact_mut(3)) {
+            let px = rgb565_to_rgb8(u16::from_be_bytes([src[0], src[1]]));
+            dst.copy_from_slice(&[px.r, px.g, px.b]);
+        }
+        OUTPIPE.write_all(&rgb).await;
+    }
+
+    OUTPIPE.write_all(b"\r\nEND SCREENSHOT\r\n").await;
+    Ok(())
+}
+
 // xs xe ys ye rgb rect
 async fn rect(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
     let rgb = unsafe { forth.data_stack.try_pop()?.data } as u16;
@@ -337,203 +686,825 @@ async fn rect(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
     Ok(())
 }
 
-struct Font<'a> {
-    font: &'a [u8],
-    font_width_chars: usize,
-    font_height_chars: usize,
-    char_width_px: usize,
-    char_height_px: usize,
+// idx x y mirror blit
+//
+// Draws one of the built in ASSETS. Mirror bit 0 flips X, bit 1 flips Y.
+async fn blit(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let mirror = unsafe { forth.data_stack.try_pop()?.data };
+    let y = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let x = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let idx = unsafe { forth.data_stack.try_pop()?.data } as usize;
+
+    let img = ASSETS.get(idx).ok_or(forth3::Error::BadLiteral)?;
+    let ctx = &mut forth.host_ctxt;
+    image::blit(&mut ctx.lcd, ctx.spif.as_mut(), img, x, y, Mirror::from_bits(mirror))
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// offset w h x y mirror blit-flash
+//
+// Draws a w x h image stored at `offset` in the external flash
+async fn blit_flash(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let mirror = unsafe { forth.data_stack.try_pop()?.data };
+    let y = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let x = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let height = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let width = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let offset = unsafe { forth.data_stack.try_pop()?.data } as u32;
+
+    let img = Image {
+        width,
+        height,
+        source: ImageSource::Flash(offset),
+    };
+    let ctx = &mut forth.host_ctxt;
+    let spif = Some(flash(&mut ctx.spif)?);
+    image::blit(&mut ctx.lcd, spif, &img, x, y, Mirror::from_bits(mirror))
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// idx blit-quad
+//
+// Draws a quarter image from ASSETS four ways, into a whole centered on screen
+async fn blit_quad(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let idx = unsafe { forth.data_stack.try_pop()?.data } as usize;
+
+    let img = ASSETS.get(idx).ok_or(forth3::Error::BadLiteral)?;
+    let ctx = &mut forth.host_ctxt;
+    image::blit_quad(&mut ctx.lcd, ctx.spif.as_mut(), img)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// rgb scene-bg
+//
+// Use a solid RGB565 color behind the sprites
+fn scene_bg(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let rgb = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    forth.host_ctxt.scene.set_background(Background::Color(rgb));
+    Ok(())
+}
+
+// idx scene-bg-quad
+//
+// Use a quarter image from ASSETS behind the sprites, like `blit-quad`
+fn scene_bg_quad(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let idx = unsafe { forth.data_stack.try_pop()?.data } as usize;
+    let img = ASSETS.get(idx).ok_or(forth3::Error::BadLiteral)?;
+    forth.host_ctxt.scene.set_background(Background::Quad(img));
+    Ok(())
+}
+
+// scene-dirty
+//
+// Redraw the whole scene on the next `scene-draw`
+fn scene_dirty(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    forth.host_ctxt.scene.mark_all();
+    Ok(())
+}
+
+// scene-draw
+//
+// Redraw the parts of the scene that changed
+async fn scene_draw(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ctx = &mut forth.host_ctxt;
+    ctx.scene
+        .render(&mut ctx.lcd, ctx.spif.as_mut())
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
 }
 
-impl<'a> Font<'a> {
-    fn char_buf_size(&self) -> usize {
-        self.char_width_px * self.char_height_px * core::mem::size_of::<u16>()
+fn new_sprite(forth: &mut Forth<RobertCtx>, slot: i32, image: Image) -> Result<(), forth3::Error> {
+    let slot = usize::try_from(slot).map_err(|_| forth3::Error::BadLiteral)?;
+    forth
+        .host_ctxt
+        .scene
+        .set(slot, Sprite::new(image))
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+fn update_sprite(
+    forth: &mut Forth<RobertCtx>,
+    slot: i32,
+    f: impl FnOnce(&mut Sprite),
+) -> Result<(), forth3::Error> {
+    let slot = usize::try_from(slot).map_err(|_| forth3::Error::BadLiteral)?;
+    forth
+        .host_ctxt
+        .scene
+        .update(slot, f)
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// slot idx sprite-asset
+//
+// Make a sprite from one of the built in ASSETS. New sprites start hidden,
+// at 0 0 on layer 0.
+fn sprite_asset(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let idx = unsafe { forth.data_stack.try_pop()?.data } as usize;
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+    let img = *ASSETS.get(idx).ok_or(forth3::Error::BadLiteral)?;
+    new_sprite(forth, slot, img)
+}
+
+// slot addr w h sprite-ram
+//
+// Make a sprite from a copy of w x h RGB565 pixels in forth memory. The
+// copies of every sprite and mask share SPRITE_RAM bytes.
+fn sprite_ram(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let height = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let width = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+
+    let slot = usize::try_from(slot).map_err(|_| forth3::Error::BadLiteral)?;
+    let len = width as i32 * height as i32 * 2;
+    let data = unsafe { forth_str(addr, len)? };
+    forth
+        .host_ctxt
+        .scene
+        .set_ram(slot, width, height, data)
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// slot offset w h sprite-flash
+//
+// Make a sprite from w x h RGB565 pixels at `offset` in the external flash
+fn sprite_flash(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let height = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let width = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let offset = unsafe { forth.data_stack.try_pop()?.data } as u32;
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+
+    let img = Image {
+        width,
+        height,
+        source: ImageSource::Flash(offset),
+    };
+    new_sprite(forth, slot, img)
+}
+
+fn mask_kind(kind: i32) -> Result<Option<MaskKind>, forth3::Error> {
+    match kind {
+        0 => Ok(None),
+        1 => Ok(Some(MaskKind::Bits)),
+        2 => Ok(Some(MaskKind::Alpha)),
+        _ => Err(forth3::Error::BadLiteral),
     }
+}
+
+// slot addr kind sprite-mask
+//
+// Give a sprite a copy of a mask in forth memory. Kind 0 removes the mask, 1
+// is 1 bit per pixel (rows padded to a whole byte), 2 is 8-bit alpha.
+fn sprite_mask(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let kind = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+
+    let kind = mask_kind(kind)?;
+    let sprite = usize::try_from(slot)
+        .ok()
+        .and_then(|s| forth.host_ctxt.scene.get(s))
+        .ok_or(forth3::Error::BadLiteral)?;
+    let (width, height) = (sprite.width as i32, sprite.height as i32);
+
+    let Some(kind) = kind else {
+        return update_sprite(forth, slot, |s| s.mask = None);
+    };
+    let row_bytes = match kind {
+        MaskKind::Bits => (width + 7) / 8,
+        MaskKind::Alpha => width,
+    };
+    let data = unsafe { forth_str(addr, row_bytes * height)? };
+    forth
+        .host_ctxt
+        .scene
+        .set_mask_ram(slot as usize, kind, data)
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// slot offset kind sprite-mask-flash
+//
+// Like `sprite-mask`, with the mask at `offset` in the external flash
+fn sprite_mask_flash(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let kind = unsafe { forth.data_stack.try_pop()?.data };
+    let offset = unsafe { forth.data_stack.try_pop()?.data } as u32;
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+
+    let mask = mask_kind(kind)?.map(|kind| Mask {
+        kind,
+        source: Source::Image(ImageSource::Flash(offset)),
+    });
+    update_sprite(forth, slot, |s| s.mask = mask)
+}
+
+// slot x y sprite-move
+fn sprite_move(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let y = unsafe { forth.data_stack.try_pop()?.data } as i16;
+    let x = unsafe { forth.data_stack.try_pop()?.data } as i16;
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+    update_sprite(forth, slot, |s| {
+        s.x = x;
+        s.y = y;
+    })
+}
+
+// slot layer sprite-layer
+fn sprite_layer(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let layer = unsafe { forth.data_stack.try_pop()?.data } as u8;
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+    update_sprite(forth, slot, |s| s.layer = layer)
+}
+
+// slot sprite-show
+fn sprite_show(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+    update_sprite(forth, slot, |s| s.visible = true)
+}
 
-    fn font_bin_size(&self) -> usize {
-        let char_ttl_px = self.char_width_px * self.char_height_px;
-        char_ttl_px * self.font_width_chars * self.font_height_chars / 8
+// slot sprite-hide
+fn sprite_hide(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+    update_sprite(forth, slot, |s| s.visible = false)
+}
+
+// slot sprite-free
+fn sprite_free(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let slot = unsafe { forth.data_stack.try_pop()?.data };
+    let slot = usize::try_from(slot).map_err(|_| forth3::Error::BadLiteral)?;
+    forth.host_ctxt.scene.remove(slot);
+    Ok(())
+}
+
+/// Most `|` separated items taken by `menu`, `list` and `alert`
+const MAX_ITEMS: usize = 16;
+
+/// Split a forth string into items at each `|`
+fn split_items(txt: &[u8]) -> Result<heapless::Vec<&[u8], MAX_ITEMS>, forth3::Error> {
+    let mut items = heapless::Vec::new();
+    for item in txt.split(|b| *b == b'|') {
+        items.push(item).map_err(|_| forth3::Error::BadLiteral)?;
     }
+    Ok(items)
+}
 
-    fn font_alpha_to_be_bytes(
-        &self,
-        buf: &mut [u8],
-        char_x: usize,
-        char_y: usize,
-        set_val: RGB8,
-        clr_val: RGB8,
-    ) -> Result<(), ()> {
-        // Break apart colors
-        let (sr, sg, sb): (u8, u8, u8) = set_val.into();
-        let (cr, cg, cb): (u8, u8, u8) = clr_val.into();
-
-        // How many pixels wide is the source font table?
-        let font_width_px = self.char_width_px * self.font_width_chars;
-
-        // One row at a time of the destination (in 16-bit 565 values)
-        let dst_rows = buf.chunks_exact_mut(self.char_width_px * 2);
-
-        // This is an iterator of all font rows
-        let rows = self.font.chunks_exact(font_width_px);
-
-        // This is only the font rows that are relevant to the character we want
-        let relevant = rows
-            .skip(self.char_height_px * char_y)
-            .take(self.char_height_px);
-
-        // For each destination row zipped with each font source row...
-        dst_rows
-            .zip(relevant)
-            .flat_map(|(dst_row, src_row)| {
-                // ... We only want the columns in each font row that are relevant
-                // for this character...
-                let cols = src_row
-                    .iter()
-                    .skip(char_x * self.char_width_px)
-                    .take(self.char_width_px);
-
-                // Then for each dest 16-bit value, apply the 1-byte alpha
-                dst_row.chunks_exact_mut(2).zip(cols)
-            })
-            .for_each(|(dst_2b, alpha_1b)| {
-                // Here "plus" is how much we take from the "set" color,
-                // and "minus" is how much we take from the "clear" color.
-                //
-                // This is to fade the transparency to the background color
-                let plus = *alpha_1b as u16;
-                let minus = 255 - plus;
-
-                // Apply alpha to each channel, extending it to 16 bits for each
-                // channel (8b x 8b = 16b)
-                let r0 = sr as u16 * plus;
-                let r1 = cr as u16 * minus;
-                let g0 = sg as u16 * plus;
-                let g1 = cg as u16 * minus;
-                let b0 = sb as u16 * plus;
-                let b1 = cb as u16 * minus;
-
-                // Then add the two halves together, and convert to 565 format
-                let r = (r0 + r1) & 0b1111100000000000;
-                let g = ((g0 + g1) & 0b1111110000000000) >> 5;
-                let b = ((b0 + b1) & 0b1111100000000000) >> 11;
-
-                // Then write to the dest buffer
-                dst_2b.copy_from_slice(&(r + g + b).to_be_bytes());
-            });
-        Ok(())
-    }
-
-    fn font_bit_to_be_bytes(
-        &self,
-        buf: &mut [u8],
-        char_x: usize,
-        char_y: usize,
-        set_val: u16,
-        clr_val: u16,
-    ) -> Result<(), ()> {
-        if buf.len() != self.char_buf_size() {
-            return Err(());
-        }
-        if self.font.len() != self.font_bin_size() {
-            return Err(());
+/// Show a widget and feed it input until it's done. Returns `None` if it
+/// was backed out of.
+async fn run_modal(
+    ctx: &mut RobertCtx,
+    mut modal: Modal<'_>,
+) -> Result<Option<i32>, forth3::Error> {
+    let font = ctx.font();
+    widgets::flush_input();
+    modal
+        .draw(&mut ctx.lcd, font, &ctx.theme)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)?;
+
+    loop {
+        let input = widgets::next_input().await;
+        ctx.power.activity(&mut ctx.lcd, true).await;
+        match modal.handle(input) {
+            Response::Ignored => {}
+            Response::Changed => {
+                modal
+                    .redraw(&mut ctx.lcd, font, &ctx.theme)
+                    .await
+                    .map_err(|_| forth3::Error::BadLiteral)?;
+            }
+            Response::Done(val) => return Ok(Some(val)),
+            Response::Cancelled => return Ok(None),
         }
+    }
+}
 
-        let row_width_bytes = (self.char_width_px * self.font_width_chars) / 8;
-
-        buf.chunks_mut(self.char_width_px * 2)
-            .zip(self.font.chunks_exact(row_width_bytes))
-            .skip(self.char_height_px * char_y)
-            .take(self.char_height_px)
-            .flat_map(|(by, bi)| {
-                by.chunks_exact_mut(16)
-                    .zip(bi.iter().skip(2 * char_x).take(2))
-            })
-            .for_each(|(by16, bi)| {
-                let mut val = *bi;
-                for by in by16.chunks_exact_mut(2) {
-                    let contents = if val & 0x80 != 0 { set_val } else { clr_val };
-                    by.copy_from_slice(&contents.to_be_bytes());
-                    val <<= 1;
-                }
-            });
+// fg bg accent track ui-colors
+//
+// RGB565 colors for the widgets
+fn ui_colors(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let track = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let accent = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let bg = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    let fg = unsafe { forth.data_stack.try_pop()?.data } as u16;
+    forth.host_ctxt.theme = Theme {
+        fg,
+        bg,
+        accent,
+        track,
+    };
+    Ok(())
+}
+
+// ui-clear
+//
+// Fill the screen with the widget background
+async fn ui_clear(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ctx = &mut forth.host_ctxt;
+    widgets::clear(&mut ctx.lcd, &ctx.theme)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// n addr len readout
+//
+// A number in the middle of the screen, with a caption under it
+async fn readout(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let value = unsafe { forth.data_stack.try_pop()?.data };
+
+    let caption = unsafe { forth_str(addr, len)? };
+    let font = forth.host_ctxt.font();
+    let ctx = &mut forth.host_ctxt;
+    Readout { value, caption }
+        .draw(&mut ctx.lcd, font, &ctx.theme)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// value min max gauge
+//
+// An arc around the rim, filled from `min` to `value`, out of `max`
+async fn gauge(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let max = unsafe { forth.data_stack.try_pop()?.data };
+    let min = unsafe { forth.data_stack.try_pop()?.data };
+    let value = unsafe { forth.data_stack.try_pop()?.data };
+
+    let font = forth.host_ctxt.font();
+    let ctx = &mut forth.host_ctxt;
+    Gauge::new(value, min, max)
+        .draw(&mut ctx.lcd, font, &ctx.theme)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// value min max adjust ( -- value' )
+//
+// Like `gauge`, then lets the value be changed with the buttons or dial
+// until select is pressed. Back leaves it unchanged.
+async fn adjust(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let max = unsafe { forth.data_stack.try_pop()?.data };
+    let min = unsafe { forth.data_stack.try_pop()?.data };
+    let value = unsafe { forth.data_stack.try_pop()?.data };
+
+    let gauge = Gauge::new(value, min, max);
+    let res = run_modal(&mut forth.host_ctxt, Modal::Gauge(gauge)).await?;
+    forth.data_stack.push(Word::data(res.unwrap_or(value)))?;
+    Ok(())
+}
 
-        Ok(())
+// pct progress
+//
+// A ring around the rim, filled clockwise from the top
+async fn progress(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let percent = unsafe { forth.data_stack.try_pop()?.data }.clamp(0, 100) as u8;
+
+    let font = forth.host_ctxt.font();
+    let ctx = &mut forth.host_ctxt;
+    Progress { percent }
+        .draw(&mut ctx.lcd, font, &ctx.theme)
+        .await
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+// addr len menu ( -- idx )
+//
+// Short `|` separated items around the rim, like s" Tea|Coffee|Water".
+// Returns the chosen item, or -1 if backed out of.
+async fn menu(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+
+    let items = split_items(unsafe { forth_str(addr, len)? })?;
+    if items.len() > widgets::MENU_MAX {
+        return Err(forth3::Error::BadLiteral);
     }
+    let res = run_modal(&mut forth.host_ctxt, Modal::Menu(Menu::new(&items))).await?;
+    forth.data_stack.push(Word::data(res.unwrap_or(-1)))?;
+    Ok(())
 }
 
-// x y font
-async fn font(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
-    let y_pos = unsafe { forth.data_stack.try_pop()?.data } as u8;
-    let mut x_pos = unsafe { forth.data_stack.try_pop()?.data } as u8;
+// addr len list ( -- idx )
+//
+// Like `menu`, as a scrolling list for longer items
+async fn list(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+
+    let items = split_items(unsafe { forth_str(addr, len)? })?;
+    let res = run_modal(&mut forth.host_ctxt, Modal::List(List::new(&items))).await?;
+    forth.data_stack.push(Word::data(res.unwrap_or(-1)))?;
+    Ok(())
+}
 
-    let mut buf = [0u8; 1024];
-    let buf = &mut buf[..FONT.char_buf_size()];
+// addr len alert
+//
+// Show `|` separated lines until select or back is pressed
+async fn alert(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
 
-    let lcd = &mut forth.host_ctxt.lcd;
+    let lines = split_items(unsafe { forth_str(addr, len)? })?;
+    run_modal(&mut forth.host_ctxt, Modal::Alert(Alert { lines: &lines })).await?;
+    Ok(())
+}
+
+// h m s clock-set
+//
+// Set the time of day, there's no RTC so this is lost on reset
+fn clock_set(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let s = unsafe { forth.data_stack.try_pop()?.data };
+    let m = unsafe { forth.data_stack.try_pop()?.data };
+    let h = unsafe { forth.data_stack.try_pop()?.data };
+
+    if !(0..24).contains(&h) || !(0..60).contains(&m) || !(0..60).contains(&s) {
+        return Err(forth3::Error::BadLiteral);
+    }
+    forth.host_ctxt.clock.set(h as u32, m as u32, s as u32);
+    Ok(())
+}
 
-    for ch in b"butts" {
-        let idx = ch - b' ';
-        let ch_x = idx % 32;
-        let ch_y = idx / 32;
+// time ( -- h m s )
+fn time(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let now = forth.host_ctxt.clock.now() as i32;
+    forth.data_stack.push(Word::data(now / 3600))?;
+    forth.data_stack.push(Word::data(now / 60 % 60))?;
+    forth.data_stack.push(Word::data(now % 60))?;
+    Ok(())
+}
 
-        FONT.font_bit_to_be_bytes(buf, ch_x.into(), ch_y.into(), 0xFFFF, 0x0000)
-            .unwrap();
+// clock
+//
+// Show the clock face until select or back is pressed
+async fn clock(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ctx = &mut forth.host_ctxt;
+    let mut face = Face::new();
+    widgets::flush_input();
 
-        lcd.draw(x_pos, x_pos + 16, y_pos, y_pos + 29, buf)
+    loop {
+        face.update(&mut ctx.lcd, ctx.spif.as_mut(), ctx.clock.now())
             .await
-            .ok();
+            .map_err(|_| forth3::Error::BadLiteral)?;
+
+        // Wake up just after the next whole second
+        let next = Instant::from_secs(Instant::now().as_secs() + 1);
+        loop {
+            match select(Timer::at(next), widgets::next_input()).await {
+                Either::First(()) => {
+                    ctx.power.tick(&mut ctx.lcd).await;
+                    break;
+                }
+                Either::Second(input) => {
+                    ctx.power.activity(&mut ctx.lcd, true).await;
+                    if let Input::Select | Input::Back = input {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
 
-        x_pos += 16;
+/// Hand a command to the buzzer task, if there's a buzzer
+fn buzzer_cmd(cmd: BuzzerCmd) -> Result<(), forth3::Error> {
+    if !buzzer::FITTED {
+        return Err(forth3::Error::BadLiteral);
     }
+    BUZZER_CMDS
+        .try_send(cmd)
+        .map_err(|_| forth3::Error::BadLiteral)
+}
 
-    Ok(())
+// hz ms tone
+//
+// Sound the buzzer without waiting for it to finish, 0 ms keeps it going
+// until notone
+fn tone(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let ms = unsafe { forth.data_stack.try_pop()?.data };
+    let hz = unsafe { forth.data_stack.try_pop()?.data };
+    let ms = u32::try_from(ms).map_err(|_| forth3::Error::BadLiteral)?;
+    let hz = u32::try_from(hz).map_err(|_| forth3::Error::BadLiteral)?;
+    buzzer_cmd(BuzzerCmd::Tone { hz, ms })
 }
 
-async fn font2(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
-    let y_pos = unsafe { forth.data_stack.try_pop()?.data } as u8;
-    let mut x_pos = unsafe { forth.data_stack.try_pop()?.data } as u8;
+// notone, also stop-music
+//
+// Silence the buzzer, stopping any tune
+fn notone(_forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    buzzer_cmd(BuzzerCmd::Off)
+}
 
-    let mut buf = [0u8; 1024];
-    let buf = &mut buf[..FONT2.char_buf_size()];
+// play" name:d=4,o=5,b=120:c,e,g"
+//
+// Play an RTTTL tune, or just a list of notes, without waiting for it. Only
+// works when interpreting, like `s"`.
+fn play_quote(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    forth
+        .input
+        .advance_str()
+        .map_err(|_| forth3::Error::LQuoteMissingRQuote)?;
+    let lit = forth
+        .input
+        .cur_str_literal()
+        .ok_or(forth3::Error::LQuoteMissingRQuote)?;
+    buzzer::play(lit.as_bytes()).map_err(|_| forth3::Error::BadLiteral)
+}
 
-    let lcd = &mut forth.host_ctxt.lcd;
+// addr len play
+fn play(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let text = unsafe { forth_str(addr, len)? };
+    buzzer::play(text).map_err(|_| forth3::Error::BadLiteral)
+}
 
-    for ch in b"butts" {
-        let idx = ch - b' ';
-        let ch_x = idx % 32;
-        let ch_y = idx / 32;
-
-        FONT2
-            .font_alpha_to_be_bytes(buf, ch_x.into(), ch_y.into(), colors::WHITE, colors::BLACK)
-            .unwrap();
-
-        lcd.draw(
-            x_pos,
-            x_pos + FONT2.char_width_px as u8,
-            y_pos,
-            y_pos + FONT2.char_height_px as u8,
-            buf,
-        )
+// addr len rate pcm
+//
+// Play 8-bit unsigned samples from memory, waiting until they've all been
+// read
+async fn pcm(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let rate = unsafe { forth.data_stack.try_pop()?.data };
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let addr = unsafe { forth.data_stack.try_pop()?.data };
+    let rate = u32::try_from(rate).map_err(|_| forth3::Error::BadLiteral)?;
+    let data = unsafe { forth_str(addr, len)? };
+
+    start_stream(data.len() as u32, rate)?;
+    for chunk in data.chunks(256) {
+        write_stream(chunk).await?;
+    }
+    Ok(())
+}
+
+// offset len rate pcm-flash
+//
+// Play 8-bit unsigned samples from the flash, waiting until they've all been
+// read
+async fn pcm_flash(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let rate = unsafe { forth.data_stack.try_pop()?.data };
+    let len = unsafe { forth.data_stack.try_pop()?.data };
+    let offset = unsafe { forth.data_stack.try_pop()?.data } as u32;
+    let rate = u32::try_from(rate).map_err(|_| forth3::Error::BadLiteral)?;
+    let len = u32::try_from(len).map_err(|_| forth3::Error::BadLiteral)?;
+    let spif = flash(&mut forth.host_ctxt.spif)?;
+
+    start_stream(len, rate)?;
+    let mut buf = [0u8; 256];
+    let mut done = 0;
+    while done < len {
+        let n = (len - done).min(buf.len() as u32) as usize;
+        spif.read(offset + done, &mut buf[..n])
+            .await
+            .map_err(|_| forth3::Error::BadLiteral)?;
+        write_stream(&buf[..n]).await?;
+        done += n as u32;
+    }
+    Ok(())
+}
+
+/// Tell the buzzer to play `len` samples from [buzzer::PCM_STREAM]
+fn start_stream(len: u32, rate: u32) -> Result<(), forth3::Error> {
+    // Throw away anything left over from a stream that was cut short
+    let mut buf = [0u8; 256];
+    while buzzer::PCM_STREAM.try_read(&mut buf).is_ok() {}
+    buzzer_cmd(BuzzerCmd::Pcm {
+        samples: Samples::Stream(len),
+        rate,
+    })
+}
+
+/// Feed the next samples to a stream started with [start_stream]
+async fn write_stream(bytes: &[u8]) -> Result<(), forth3::Error> {
+    // The buzzer gives up on a stalled stream, so don't wait forever
+    let write = buzzer::PCM_STREAM.write_all(bytes);
+    with_timeout(Duration::from_secs(1), write)
         .await
-        .ok();
+        .map_err(|_| forth3::Error::BadLiteral)
+}
+
+/// Words bound with on-press, on-release and on-long, by button then
+/// [ON_PRESS], [ON_RELEASE] or [ON_LONG]. The REPL runs them between lines.
+static BUTTON_WORDS: Mutex<ThreadModeRawMutex, Cell<[[Option<i32>; 3]; 6]>> =
+    Mutex::new(Cell::new([[None; 3]; 6]));
+const ON_PRESS: usize = 0;
+const ON_RELEASE: usize = 1;
+const ON_LONG: usize = 2;
+
+/// The word bound to `event`, if any
+fn button_word(event: ButtonEvent) -> Option<i32> {
+    let (button, kind) = match event {
+        ButtonEvent::Pressed(b) => (b, ON_PRESS),
+        ButtonEvent::Released(b) => (b, ON_RELEASE),
+        ButtonEvent::LongPress(b) => (b, ON_LONG),
+        _ => return None,
+    };
+    BUTTON_WORDS.lock(|words| words.get()[button as usize][kind])
+}
+
+/// Bind the word under the button number on the stack, 0 unbinds
+fn bind_button(forth: &mut Forth<RobertCtx>, kind: usize) -> Result<(), forth3::Error> {
+    let idx = unsafe { forth.data_stack.try_pop()?.data };
+    let xt = unsafe { forth.data_stack.try_pop()?.data };
+    let button = usize::try_from(idx)
+        .ok()
+        .and_then(|idx| Button::ALL.get(idx))
+        .ok_or(forth3::Error::BadLiteral)?;
+    BUTTON_WORDS.lock(|words| {
+        let mut all = words.get();
+        all[*button as usize][kind] = (xt != 0).then_some(xt);
+        words.set(all);
+    });
+    Ok(())
+}
 
-        x_pos += FONT2.char_width_px as u8;
+// xt idx on-press
+//
+// Run a word whenever a button, 0 to 5, is pressed. `' myword 3 on-press`.
+// Rebinding replaces the word, and an xt of 0 removes it. `forget` removes
+// every binding, as the words they run might be gone. Output and errors
+// show up like a typed line's.
+fn on_press(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    bind_button(forth, ON_PRESS)
+}
+
+// xt idx on-release
+fn on_release(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    bind_button(forth, ON_RELEASE)
+}
+
+// xt idx on-long
+//
+// Held for half a second
+fn on_long(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    bind_button(forth, ON_LONG)
+}
+
+// forget name
+//
+// The usual `forget`, which also unbinds the buttons so they can't run a
+// word that isn't there any more
+fn forget(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    Forth::forget(forth)?;
+    BUTTON_WORDS.lock(|words| words.set([[None; 3]; 6]));
+    Ok(())
+}
+
+/// Hand a command for the current strip to the smart-LED task
+fn smartled_cmd(forth: &mut Forth<RobertCtx>, cmd: SmartLedCmd) -> Result<(), forth3::Error> {
+    let strip = forth.host_ctxt.strip;
+    if strip as usize >= smartled::STRIPS.len() {
+        return Err(forth3::Error::BadLiteral);
     }
+    SMARTLED_CMDS
+        .try_send(StripCmd { strip, cmd })
+        .map_err(|_| forth3::Error::BadLiteral)
+}
 
+/// Pixels on the current strip, 0 if there isn't one
+fn strip_len(forth: &Forth<RobertCtx>) -> usize {
+    smartled::STRIPS
+        .get(forth.host_ctxt.strip as usize)
+        .map_or(0, |info| info.len)
+}
+
+// strip smartled-strip
+//
+// Pick which strip the other smart-LED words act on
+fn smartled_strip(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let strip = unsafe { forth.data_stack.try_pop()?.data };
+    let strip = u8::try_from(strip).map_err(|_| forth3::Error::BadLiteral)?;
+    if strip as usize >= smartled::STRIPS.len() {
+        return Err(forth3::Error::BadLiteral);
+    }
+    forth.host_ctxt.strip = strip;
     Ok(())
 }
 
-// fn set_gamma(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
-//     let val = unsafe { forth.data_stack.try_pop()?.data };
-//     forth.host_ctxt.enable_gamma = val != 0;
-//     Ok(())
-// }
+// idx color set_smartled
+fn set_smartled(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let color = i32_to_rgb(unsafe { forth.data_stack.try_pop()?.data });
+    let idx = unsafe { forth.data_stack.try_pop()?.data };
+    let idx = u16::try_from(idx).map_err(|_| forth3::Error::BadLiteral)?;
+    if idx as usize >= strip_len(forth) {
+        return Err(forth3::Error::BadLiteral);
+    }
+    smartled_cmd(forth, SmartLedCmd::Set { idx, color })
+}
 
-// fn set_brightness(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
-//     let val = unsafe { forth.data_stack.try_pop()?.data } as u8;
-//     forth.host_ctxt.brightness = val;
-//     Ok(())
-// }
+// color fill_smartled
+fn fill_smartled(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let color = i32_to_rgb(unsafe { forth.data_stack.try_pop()?.data });
+    smartled_cmd(forth, SmartLedCmd::Fill(color))
+}
+
+// smartled_off
+//
+// Also switches off the strip's power
+fn smartled_off(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    smartled_cmd(forth, SmartLedCmd::Fill(colors::BLACK))
+}
+
+// on? set_gamma
+fn set_gamma(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let val = unsafe { forth.data_stack.try_pop()?.data };
+    smartled_cmd(forth, SmartLedCmd::Gamma(val != 0))
+}
+
+// level set_brightness
+//
+// Scale every pixel on the strip, 0..=255, where 255 is smartled::MAX_BRIGHTNESS
+fn set_brightness(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let val = pop_u8(forth)?;
+    smartled_cmd(forth, SmartLedCmd::Brightness(val))
+}
+
+// smartled_len
+fn smartled_len(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    forth.data_stack.push(Word::data(strip_len(forth) as i32))?;
+    Ok(())
+}
+
+/// Pop the `color time` taken by most of the strip effects
+fn smartled_args(forth: &mut Forth<RobertCtx>) -> Result<(RGB8, u32), forth3::Error> {
+    let time = unsafe { forth.data_stack.try_pop()?.data };
+    let color = i32_to_rgb(unsafe { forth.data_stack.try_pop()?.data });
+    let time = u32::try_from(time).map_err(|_| forth3::Error::BadLiteral)?;
+    Ok((color, time))
+}
+
+/// Pop a 0..=255 amount
+fn pop_u8(forth: &mut Forth<RobertCtx>) -> Result<u8, forth3::Error> {
+    let val = unsafe { forth.data_stack.try_pop()?.data };
+    Ok(val.clamp(0, u8::MAX.into()) as u8)
+}
+
+// period smartled-rainbow
+fn smartled_rainbow(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let period = unsafe { forth.data_stack.try_pop()?.data };
+    let period = u32::try_from(period).map_err(|_| forth3::Error::BadLiteral)?;
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Rainbow { period }))
+}
+
+// color step smartled-chase
+fn smartled_chase(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (color, step) = smartled_args(forth)?;
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Chase { color, step }))
+}
+
+// color ms smartled-wipe
+fn smartled_wipe(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (color, ms) = smartled_args(forth)?;
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Wipe { color, ms }))
+}
+
+// color rate smartled-twinkle
+//
+// `rate` is the chance out of 256 of each pixel lighting up each frame
+fn smartled_twinkle(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let rate = pop_u8(forth)?;
+    let color = i32_to_rgb(unsafe { forth.data_stack.try_pop()?.data });
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Twinkle { color, rate }))
+}
+
+// cooling sparking smartled-fire
+//
+// Both 0..=255, 55 120 is a good start
+fn smartled_fire(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let sparking = pop_u8(forth)?;
+    let cooling = pop_u8(forth)?;
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Fire { cooling, sparking }))
+}
+
+// color period smartled-comet
+fn smartled_comet(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (color, period) = smartled_args(forth)?;
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Comet { color, period }))
+}
+
+// color period smartled-breathe
+fn smartled_breathe(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let (color, period) = smartled_args(forth)?;
+    smartled_cmd(forth, SmartLedCmd::Play(SmartEffect::Breathe { color, period }))
+}
+
+// smartled-stop
+//
+// Stop the effect, leaving the strip showing its last frame
+fn smartled_stop(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    smartled_cmd(forth, SmartLedCmd::Stop)
+}
+
+// fps smartled-fps
+//
+// Frame rate of the effects on every strip
+fn smartled_fps(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
+    let fps = unsafe { forth.data_stack.try_pop()?.data };
+    let fps = u32::try_from(fps).map_err(|_| forth3::Error::BadLiteral)?;
+    smartled_cmd(forth, SmartLedCmd::Fps(fps))
+}
 
 // fn red_const(forth: &mut Forth<RobertCtx>) -> Result<(), forth3::Error> {
 //     forth.data_stack.push(Word::data(RED))?;
@@ -595,16 +1566,38 @@ impl<'forth> AsyncBuiltins<'forth, RobertCtx> for RobertAsync {
         async_builtin!("sleep::ms"),
         async_builtin!("reboot"),
         async_builtin!("flush"),
-        // async_builtin!("set_smartled"),
-        // async_builtin!("smartled_off"),
         async_builtin!("init"),
         async_builtin!("init_lcd"),
         async_builtin!("rect"),
-        async_builtin!("font"),
-        async_builtin!("font2"),
+        async_builtin!("rotate"),
+        async_builtin!("blit"),
+        async_builtin!("blit-flash"),
+        async_builtin!("blit-quad"),
+        async_builtin!("mirror"),
         async_builtin!("blank_line"),
         async_builtin!("print_line"),
+        async_builtin!("font"),
+        async_builtin!("font2"),
+        async_builtin!("draw-text"),
+        async_builtin!("center-text"),
         async_builtin!("get_spi_id"),
+        async_builtin!("fade"),
+        async_builtin!("lcd-sleep"),
+        async_builtin!("lcd-wake"),
+        async_builtin!("lcd-reinit"),
+        async_builtin!("screenshot"),
+        async_builtin!("scene-draw"),
+        async_builtin!("ui-clear"),
+        async_builtin!("readout"),
+        async_builtin!("gauge"),
+        async_builtin!("adjust"),
+        async_builtin!("progress"),
+        async_builtin!("menu"),
+        async_builtin!("list"),
+        async_builtin!("alert"),
+        async_builtin!("clock"),
+        async_builtin!("pcm"),
+        async_builtin!("pcm-flash"),
     ];
 
     fn dispatch_async(
@@ -639,30 +1632,36 @@ impl<'forth> AsyncBuiltins<'forth, RobertCtx> for RobertAsync {
                 }
                 "init_lcd" => init_disp(forth).await,
                 "rect" => rect(forth).await,
-                "font" => font(forth).await,
-                "font2" => font2(forth).await,
+                "rotate" => rotate(forth).await,
+                "blit" => blit(forth).await,
+                "blit-flash" => blit_flash(forth).await,
+                "blit-quad" => blit_quad(forth).await,
+                "mirror" => mirror(forth).await,
                 "blank_line" => blank_line(forth).await,
                 "print_line" => print_line(forth).await,
+                "font" => font(forth).await,
+                "font2" => font2(forth).await,
+                "draw-text" => draw_text(forth).await,
+                "center-text" => center_text(forth).await,
                 "init" => init(forth).await,
                 "get_spi_id" => get_spi_id(forth).await,
-                // "set_smartled" => {
-                //     let val = forth.data_stack.try_pop()?;
-                //     let val = unsafe { val.data };
-                //     let mut data = i32_to_rgb(val);
-
-                //     if forth.host_ctxt.enable_gamma {
-                //         data = gamma_one(data);
-                //     }
-
-                //     data = brightness_one(data, forth.host_ctxt.brightness);
-
-                //     forth.host_ctxt.ws2812.write(&[data]).await;
-                //     Ok(())
-                // }
-                // "smartled_off" => {
-                //     forth.host_ctxt.ws2812.write(&[colors::BLACK]).await;
-                //     Ok(())
-                // }
+                "fade" => fade(forth).await,
+                "lcd-sleep" => lcd_sleep(forth).await,
+                "lcd-wake" => lcd_wake(forth).await,
+                "lcd-reinit" => lcd_reinit(forth).await,
+                "screenshot" => screenshot(forth).await,
+                "scene-draw" => scene_draw(forth).await,
+                "ui-clear" => ui_clear(forth).await,
+                "readout" => readout(forth).await,
+                "gauge" => gauge(forth).await,
+                "adjust" => adjust(forth).await,
+                "progress" => progress(forth).await,
+                "menu" => menu(forth).await,
+                "list" => list(forth).await,
+                "alert" => alert(forth).await,
+                "clock" => clock(forth).await,
+                "pcm" => pcm(forth).await,
+                "pcm-flash" => pcm_flash(forth).await,
                 _ => Err(forth3::Error::WordNotInDict),
             }
         }
@@ -706,6 +1705,11 @@ static OUTBUF: MemChunk<u8, 128> = MemChunk::uninit();
 static DICTS: MemChunk<DictBuf<DICT_BUF_LEN>, 1> = MemChunk::uninit();
 pub static INPIPE: Pipe<ThreadModeRawMutex, 256> = Pipe::new();
 pub static OUTPIPE: Pipe<ThreadModeRawMutex, 256> = Pipe::new();
+/// Held while the REPL needs [OUTPIPE] to itself, like for a screenshot.
+/// Other tasks take it around each write, so their text can't end up in
+/// the middle.
+pub static OUTPIPE_LOCK: embassy_sync::mutex::Mutex<ThreadModeRawMutex, ()> =
+    embassy_sync::mutex::Mutex::new(());
 
 unsafe fn buffers() -> Buffers<RobertCtx> {
     // TODO: Singleton check
@@ -731,6 +1735,35 @@ pub unsafe fn forth(ctx: RobertCtx) -> AsyncForth<RobertCtx, RobertAsync> {
     AsyncForth::new(buffers(), dict(), ctx, ROBERT_BUILTINS, RobertAsync {}).unwrap()
 }
 
+/// How often the REPL checks the display power timeouts
+const IDLE_POLL: Duration = Duration::from_millis(250);
+
+/// Run a line, writing its output or error to [OUTPIPE]
+async fn run_line(forth: &mut AsyncForth<RobertCtx, RobertAsync>, line: &str) {
+    forth.input_mut().fill(line).unwrap();
+    match forth.process_line().await {
+        Ok(()) => {
+            let om = forth.output_mut();
+            let out = om.as_str().as_bytes();
+            OUTPIPE.write_all(out).await;
+            OUTPIPE.write_all(b"\r").await;
+        }
+        Err(e) => {
+            OUTPIPE.write_all(b"ERROR\r\n").await;
+            let es = err2str(&e);
+            OUTPIPE.write_all(es.as_bytes()).await;
+            OUTPIPE.write_all(b"\r\n").await;
+        }
+    }
+    // TODO(ajm): I need a "clear" function for the input. This wont properly
+    // clear string literals either.
+    let inp = forth.input_mut();
+    while inp.cur_word().is_some() {
+        inp.advance();
+    }
+    forth.output_mut().clear();
+}
+
 #[embassy_executor::task]
 pub async fn run_forth(ctx: RobertCtx) {
     let mut forth = unsafe { forth(ctx) };
@@ -738,46 +1771,68 @@ pub async fn run_forth(ctx: RobertCtx) {
     let mut strbuf = heapless::Vec::<u8, 128>::new();
     OUTPIPE.write_all(b"RP2040 Forth Says Hello!\r\n").await;
     loop {
-        let ilen = INPIPE.read(&mut ibuf).await;
+        let ilen = match select4(
+            INPIPE.read(&mut ibuf),
+            BUTTON_ACTIVITY.wait(),
+            Timer::after(IDLE_POLL),
+            BUTTON_EVENTS.receive(),
+        )
+        .await
+        {
+            Either4::First(ilen) => {
+                let ctx = forth.host_ctxt_mut();
+                ctx.power.activity(&mut ctx.lcd, false).await;
+                ilen
+            }
+            Either4::Second(()) => {
+                let ctx = forth.host_ctxt_mut();
+                ctx.power.activity(&mut ctx.lcd, true).await;
+                continue;
+            }
+            Either4::Third(()) => {
+                let ctx = forth.host_ctxt_mut();
+                ctx.power.tick(&mut ctx.lcd).await;
+                continue;
+            }
+            Either4::Fourth(event) => {
+                if let Some(xt) = button_word(event) {
+                    let mut line = heapless::String::<24>::new();
+                    if write!(&mut line, "{xt} execute").is_ok() {
+                        run_line(&mut forth, &line).await;
+                    }
+                }
+                continue;
+            }
+        };
         for chb in &ibuf[..ilen] {
             let is_ascii = chb.is_ascii();
             let is_control = chb.is_ascii_control();
             match (is_ascii, is_control, *chb) {
-                (true, false, _) => {
+                // Printable ASCII, or part of a UTF-8 character, for
+                // strings with accents or symbols in them
+                (true, false, _) | (false, _, _) => {
                     let bstr = &[*chb];
                     OUTPIPE.write_all(bstr).await;
                     strbuf.push(*chb).unwrap();
                 }
                 (true, true, b'\r') | (true, true, b'\n') => {
-                    let s = core::str::from_utf8(strbuf.as_slice()).unwrap();
-                    forth.input_mut().fill(s).unwrap();
-                    strbuf.clear();
+                    let Ok(s) = core::str::from_utf8(strbuf.as_slice()) else {
+                        OUTPIPE.write_all(b"\r\nERROR\r\nInvalid UTF-8\r\n").await;
+                        strbuf.clear();
+                        continue;
+                    };
                     OUTPIPE.write_all(b"\r\n").await;
-                    match forth.process_line().await {
-                        Ok(()) => {
-                            let om = forth.output_mut();
-                            let out = om.as_str().as_bytes();
-                            OUTPIPE.write_all(out).await;
-                            OUTPIPE.write_all(b"\r").await;
-                        }
-                        Err(e) => {
-                            OUTPIPE.write_all(b"ERROR\r\n").await;
-                            let es = err2str(&e);
-                            OUTPIPE.write_all(es.as_bytes()).await;
-                            OUTPIPE.write_all(b"\r\n").await;
-                        }
-                    }
-                    // TODO(ajm): I need a "clear" function for the input. This wont properly
-                    // clear string literals either.
-                    let inp = forth.input_mut();
-                    while inp.cur_word().is_some() {
-                        inp.advance();
-                    }
-                    forth.output_mut().clear();
+                    run_line(&mut forth, s).await;
+                    strbuf.clear();
                 }
                 (true, true, 0x7f) | (true, true, 0x08) => {
-                    if strbuf.pop().is_some() {
-                        OUTPIPE.write_all(&[0x08, b' ', 0x08]).await;
+                    // Remove a whole character, including any UTF-8
+                    // continuation bytes
+                    while let Some(b) = strbuf.pop() {
+                        if b & 0b1100_0000 != 0b1000_0000 {
+                            OUTPIPE.write_all(&[0x08, b' ', 0x08]).await;
+                            break;
+                        }
                     }
                 }
                 _ => {
@@ -822,10 +1877,62 @@ pub const ROBERT_BUILTINS: &[BuiltinEntry<RobertCtx>] = &[
     // builtin!("blue", blue_const),
     builtin!("wheel", conv_wheel),
     builtin!("rgb", vals_to_rgb),
-    // builtin!("set_gamma", set_gamma),
-    // builtin!("set_brightness", set_brightness),
+    builtin!("s\"", s_quote),
+    builtin!("font-select", font_select),
+    builtin!("font-metrics", font_metrics),
+    builtin!("text-width", forth_text_width),
+    builtin!("scene-bg", scene_bg),
+    builtin!("scene-bg-quad", scene_bg_quad),
+    builtin!("scene-dirty", scene_dirty),
+    builtin!("sprite-asset", sprite_asset),
+    builtin!("sprite-ram", sprite_ram),
+    builtin!("sprite-flash", sprite_flash),
+    builtin!("sprite-mask", sprite_mask),
+    builtin!("sprite-mask-flash", sprite_mask_flash),
+    builtin!("sprite-move", sprite_move),
+    builtin!("sprite-layer", sprite_layer),
+    builtin!("sprite-show", sprite_show),
+    builtin!("sprite-hide", sprite_hide),
+    builtin!("sprite-free", sprite_free),
+    builtin!("fonts", list_fonts),
+    builtin!("ui-colors", ui_colors),
+    builtin!("clock-set", clock_set),
+    builtin!("time", time),
+    builtin!("on-press", on_press),
+    builtin!("on-release", on_release),
+    builtin!("on-long", on_long),
+    builtin!("tone", tone),
+    builtin!("notone", notone),
+    builtin!("play\"", play_quote),
+    builtin!("play", play),
+    builtin!("stop-music", notone),
+    builtin!("set_smartled", set_smartled),
+    builtin!("fill_smartled", fill_smartled),
+    builtin!("smartled_off", smartled_off),
+    builtin!("smartled_len", smartled_len),
+    builtin!("smartled-strip", smartled_strip),
+    builtin!("smartled-rainbow", smartled_rainbow),
+    builtin!("smartled-chase", smartled_chase),
+    builtin!("smartled-wipe", smartled_wipe),
+    builtin!("smartled-twinkle", smartled_twinkle),
+    builtin!("smartled-fire", smartled_fire),
+    builtin!("smartled-comet", smartled_comet),
+    builtin!("smartled-breathe", smartled_breathe),
+    builtin!("smartled-stop", smartled_stop),
+    builtin!("smartled-fps", smartled_fps),
+    builtin!("set_gamma", set_gamma),
+    builtin!("set_brightness", set_brightness),
     builtin!("set_backlight", set_backlight),
+    builtin!("brightness", brightness),
+    builtin!("lcd-timeout", lcd_timeout),
     builtin!("set_led", set_led),
+    builtin!("led-master", led_master),
+    builtin!("led-pwm", led_pwm),
+    builtin!("led-fade", led_fade),
+    builtin!("led-breathe", led_breathe),
+    builtin!("led-blink", led_blink),
+    builtin!("led-heartbeat", led_heartbeat),
+    builtin!("led-pulse", led_pulse),
     //
     // Math operations
     //
@@ -898,7 +2005,7 @@ pub const ROBERT_BUILTINS: &[BuiltinEntry<RobertCtx>] = &[
     // Define/forget
     //
     builtin!(":", Forth::colon),
-    builtin!("forget", Forth::forget),
+    builtin!("forget", forget),
     //
     // Stack/Retstack operations
     //
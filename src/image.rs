@@ -0,0 +1,236 @@
+//! RGB565 images, blitted straight to the display
+//!
+//! Images are stored as big-endian RGB565, row by row, the same format the
+//! panel expects on the wire. They can live in the firmware image, or at an
+//! offset in the external SPI flash, and are streamed through a small buffer
+//! either way, so they never need to fit in RAM.
+
+use crate::{lcd::LcdPins, spiflash::SpiFlash};
+
+#[derive(Clone, Copy)]
+pub enum ImageSource {
+    /// Bytes baked into the firmware with `include_bytes!`
+    Static(&'static [u8]),
+    /// Bytes starting at an offset in the external flash
+    Flash(u32),
+}
+
+#[derive(Clone, Copy)]
+pub struct Image {
+    pub width: u8,
+    pub height: u8,
+    pub source: ImageSource,
+}
+
+impl ImageSource {
+    /// Read `buf.len()` bytes starting `offset` bytes in. The flash is only
+    /// needed for [ImageSource::Flash].
+    pub async fn read(
+        &self,
+        spif: Option<&mut SpiFlash>,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<(), ()> {
+        match self {
+            ImageSource::Static(data) => {
+                let src = data
+                    .get(offset..)
+                    .and_then(|d| d.get(..buf.len()))
+                    .ok_or(())?;
+                buf.copy_from_slice(src);
+                Ok(())
+            }
+            ImageSource::Flash(base) => spif
+                .ok_or(())?
+                .read(base + offset as u32, buf)
+                .await
+                .map_err(drop),
+        }
+    }
+}
+
+impl Image {
+    pub fn row_bytes(&self) -> usize {
+        self.width as usize * 2
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.row_bytes() * self.height as usize
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Mirror {
+    /// Flip left to right
+    pub x: bool,
+    /// Flip top to bottom
+    pub y: bool,
+}
+
+impl Mirror {
+    /// Bit 0 is X, bit 1 is Y
+    pub fn from_bits(bits: i32) -> Self {
+        Self {
+            x: (bits & 0b01) != 0,
+            y: (bits & 0b10) != 0,
+        }
+    }
+}
+
+// Converted and size checked from `assets/` by `build.rs`. Contains
+// `QUARTER_CIRCLE`, the top left quarter of a circle, 120x120.
+include!(concat!(env!("OUT_DIR"), "/images.rs"));
+
+/// Images that can be picked by index from forth
+pub const ASSETS: &[Image] = &[QUARTER_CIRCLE];
+
+const BUF_SIZE: usize = 4096;
+
+/// Draw `img` with its top left corner at `x`, `y`
+pub async fn blit(
+    lcd: &mut LcdPins,
+    mut spif: Option<&mut SpiFlash>,
+    img: &Image,
+    x: u8,
+    y: u8,
+    mirror: Mirror,
+) -> Result<(), ()> {
+    let row_bytes = img.row_bytes();
+    let end_x = x as usize + img.width as usize;
+    let end_y = y as usize + img.height as usize;
+
+    if img.width == 0 || img.height == 0 || end_x > 240 || end_y > 240 {
+        return Err(());
+    }
+    if let ImageSource::Static(data) = img.source {
+        if data.len() != img.size_bytes() {
+            return Err(());
+        }
+    }
+
+    let mut buf = [0u8; BUF_SIZE];
+    let rows_per_chunk = (BUF_SIZE / row_bytes).min(img.height as usize);
+    let height = img.height as usize;
+
+    lcd.start_write(x, end_x as u8, y, end_y as u8)
+        .await
+        .map_err(drop)?;
+
+    let mut res = Ok(());
+    let mut row = 0;
+    while row < height {
+        let rows = rows_per_chunk.min(height - row);
+        let chunk = &mut buf[..rows * row_bytes];
+
+        // When flipped top to bottom, the destination rows
+        // [row, row + rows) come from the other end of the source
+        let src_row = if mirror.y { height - row - rows } else { row };
+        let src_offset = src_row * row_bytes;
+
+        if img
+            .source
+            .read(spif.as_deref_mut(), src_offset, chunk)
+            .await
+            .is_err()
+        {
+            res = Err(());
+            break;
+        }
+
+        mirror_chunk(chunk, row_bytes, mirror);
+
+        if lcd.write_pixels(chunk).await.is_err() {
+            res = Err(());
+            break;
+        }
+        row += rows;
+    }
+
+    lcd.end_write();
+    res
+}
+
+/// Draw a quarter image four times around the center of the screen,
+/// mirrored so that a top left quarter becomes a whole.
+pub async fn blit_quad(
+    lcd: &mut LcdPins,
+    mut spif: Option<&mut SpiFlash>,
+    img: &Image,
+) -> Result<(), ()> {
+    let left = 120u8.checked_sub(img.width).ok_or(())?;
+    let top = 120u8.checked_sub(img.height).ok_or(())?;
+
+    for (x, y, mx, my) in [
+        (left, top, false, false),
+        (120, top, true, false),
+        (left, 120, false, true),
+        (120, 120, true, true),
+    ] {
+        blit(lcd, spif.as_deref_mut(), img, x, y, Mirror { x: mx, y: my }).await?;
+    }
+
+    Ok(())
+}
+
+/// Fill `line` with row `y` of a quarter image mirrored four ways around the
+/// center, like [blit_quad] draws it, starting at column `x`. Anything
+/// outside the image is black. `scratch` needs to hold a row of the image.
+pub async fn quad_row(
+    spif: Option<&mut SpiFlash>,
+    img: &Image,
+    y: usize,
+    x: usize,
+    line: &mut [u16],
+    scratch: &mut [u8],
+) -> Result<(), ()> {
+    let Some(iy) = quad_index(y, img.height as usize) else {
+        line.fill(0);
+        return Ok(());
+    };
+
+    let row = scratch.get_mut(..img.row_bytes()).ok_or(())?;
+    img.source.read(spif, iy * img.row_bytes(), row).await?;
+
+    for (i, px) in line.iter_mut().enumerate() {
+        *px = match quad_index(x + i, img.width as usize) {
+            Some(ix) => u16::from_be_bytes([row[ix * 2], row[ix * 2 + 1]]),
+            None => 0,
+        };
+    }
+    Ok(())
+}
+
+/// Which row or column of a quarter image `len` long shows at `pos`, when
+/// mirrored around the center of the screen
+fn quad_index(pos: usize, len: usize) -> Option<usize> {
+    let center = 120;
+    if pos < center {
+        // Ends at the center, anything bigger than a quarter is cropped
+        (pos + len).checked_sub(center)
+    } else {
+        let off = pos - center;
+        (off < len).then(|| len - 1 - off)
+    }
+}
+
+/// Apply `mirror` to a buffer of whole rows, in place
+fn mirror_chunk(chunk: &mut [u8], row_bytes: usize, mirror: Mirror) {
+    if mirror.y {
+        let rows = chunk.len() / row_bytes;
+        for i in 0..(rows / 2) {
+            let (top, bottom) = chunk.split_at_mut((rows - 1 - i) * row_bytes);
+            top[i * row_bytes..][..row_bytes].swap_with_slice(&mut bottom[..row_bytes]);
+        }
+    }
+
+    if mirror.x {
+        for row in chunk.chunks_exact_mut(row_bytes) {
+            let px = row.len() / 2;
+            for i in 0..(px / 2) {
+                let j = px - 1 - i;
+                row.swap(2 * i, 2 * j);
+                row.swap(2 * i + 1, 2 * j + 1);
+            }
+        }
+    }
+}
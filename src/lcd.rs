@@ -6,16 +6,146 @@
 // * 12 - LCD Reset
 // * 25 - LCD Backlight
 
-use embassy_rp::{spi::Spi, gpio::{AnyPin, Output}, peripherals::{SPI1, PWM_CH4}};
+use embassy_rp::{spi::Spi, gpio::{AnyPin, Output}, pwm::Pwm};
+use embassy_time::{Duration, Timer};
 
-use crate::gc9a01a::registers::{GC9A01A_CASET, GC9A01A_PASET, GC9A01A_RAMWR};
+use crate::{
+    board,
+    fmath::{perceptual, perceptual_level},
+    gc9a01a::registers::{
+        InitOp, GC9A01A_CASET, GC9A01A_DISPOFF, GC9A01A_DISPON, GC9A01A_MADCTL, GC9A01A_PASET,
+        GC9A01A_RAMWR, GC9A01A_SLPIN, GC9A01A_SLPOUT, GC9A01A_SWRESET, INIT_SEQ, MADCTL_BGR,
+        MADCTL_MV, MADCTL_MX, MADCTL_MY,
+    },
+    shadow::Shadow,
+};
 
 pub struct LcdPins {
-    pub spi: Spi<'static, SPI1, embassy_rp::spi::Async>,
+    pub spi: Spi<'static, board::LcdSpi, embassy_rp::spi::Async>,
     pub dc: Output<'static, AnyPin>,
     pub cs: Output<'static, AnyPin>,
     pub rst: Output<'static, AnyPin>,
-    pub backlight: embassy_rp::pwm::Pwm<'static, PWM_CH4>,
+    pub backlight: Backlight,
+    pub orientation: Orientation,
+    pub state: LcdState,
+    /// A copy of what's been drawn, see [crate::shadow]
+    pub shadow: Shadow,
+}
+
+/// What the controller is currently doing.
+///
+/// * `Uninit` -> `On` with [LcdPins::init]
+/// * `On` <-> `Dimmed` as the backlight is turned down and back up
+/// * `On` or `Dimmed` -> `Asleep` with [LcdPins::sleep]
+/// * `Asleep` -> `On` with [LcdPins::wake]
+/// * anything -> `Uninit` with a hard or soft reset
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum LcdState {
+    /// Not yet initialized, or just reset
+    Uninit,
+    /// Initialized, displaying at full brightness
+    On,
+    /// Displaying, with the backlight turned down
+    Dimmed,
+    /// Display off and controller in sleep mode
+    Asleep,
+}
+
+/// How often the backlight duty is updated during a fade
+const FADE_STEP: Duration = Duration::from_millis(10);
+
+pub struct Backlight {
+    pwm: Pwm<'static, board::BacklightPwm>,
+    duty: u16,
+}
+
+impl Backlight {
+    pub fn new(pwm: Pwm<'static, board::BacklightPwm>) -> Self {
+        let mut bl = Self { pwm, duty: 0 };
+        bl.set_duty(0);
+        bl
+    }
+
+    pub fn duty(&self) -> u16 {
+        self.duty
+    }
+
+    /// Set the raw 16-bit PWM duty
+    pub fn set_duty(&mut self, duty: u16) {
+        self.pwm.set_config(&board::BACKLIGHT_OUTPUT.config(duty));
+        self.duty = duty;
+    }
+
+    /// The current perceptual brightness, 0..=255
+    pub fn level(&self) -> u8 {
+        perceptual_level(self.duty)
+    }
+
+    /// Set a perceptual brightness, 0..=255
+    pub fn set_level(&mut self, level: u8) {
+        self.set_duty(perceptual(level));
+    }
+
+    /// Fade from the current brightness to `level` over `time`.
+    ///
+    /// The fade steps through perceptual levels rather than raw duty, so it
+    /// looks even all the way down instead of dropping off at the end.
+    pub async fn fade_to(&mut self, level: u8, time: Duration) {
+        let start = self.level() as i32;
+        let end = level as i32;
+        let steps = (time.as_ticks() / FADE_STEP.as_ticks()).max(1) as i32;
+
+        for step in 1..=steps {
+            let lvl = start + ((end - start) * step) / steps;
+            self.set_level(lvl as u8);
+            if step != steps {
+                Timer::after(FADE_STEP).await;
+            }
+        }
+    }
+}
+
+/// How the picture is laid onto the panel.
+///
+/// The panel is square, so every orientation keeps the same 240x240
+/// coordinate space, and all drawing goes through `CASET`/`PASET` in those
+/// logical coordinates. Changing the orientation only changes how the
+/// controller maps them onto the glass.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Orientation {
+    /// Number of quarter turns, 0..=3
+    pub rotation: u8,
+    /// Flip left to right, after rotating
+    pub mirror_x: bool,
+    /// Flip top to bottom, after rotating
+    pub mirror_y: bool,
+}
+
+impl Orientation {
+    pub fn madctl(&self) -> u8 {
+        let mut val = match self.rotation & 0b11 {
+            0 => MADCTL_MX,
+            1 => MADCTL_MV,
+            2 => MADCTL_MY,
+            _ => MADCTL_MX | MADCTL_MY | MADCTL_MV,
+        };
+
+        // With MV set, the column and page counters are exchanged, so
+        // a horizontal flip of the picture is a flip of the row order.
+        let (flip_col, flip_row) = if (val & MADCTL_MV) != 0 {
+            (self.mirror_y, self.mirror_x)
+        } else {
+            (self.mirror_x, self.mirror_y)
+        };
+        if flip_col {
+            val ^= MADCTL_MX;
+        }
+        if flip_row {
+            val ^= MADCTL_MY;
+        }
+
+        val | MADCTL_BGR
+    }
 }
 
 pub struct LcdBuf {
@@ -104,6 +234,128 @@ impl LcdBuf {
 // 195-225     64-176      7
 
 impl LcdPins {
+    /// Store the orientation and send it to the panel.
+    ///
+    /// This does not redraw anything, the current contents will appear
+    /// rotated until they are drawn again. The shadow copy is rearranged
+    /// to match.
+    pub async fn set_orientation(
+        &mut self,
+        orientation: Orientation,
+    ) -> Result<(), embassy_rp::spi::Error> {
+        self.shadow
+            .reorient(self.orientation.madctl(), orientation.madctl());
+        self.orientation = orientation;
+        self.command(&[GC9A01A_MADCTL]).await?;
+        self.data(&[orientation.madctl()]).await?;
+        Ok(())
+    }
+
+    /// Pulse the RST line, leaving the controller in its power-on state.
+    pub async fn hard_reset(&mut self) {
+        self.cs.set_high();
+        self.rst.set_high();
+        Timer::after(Duration::from_millis(5)).await;
+        self.rst.set_low();
+        Timer::after(Duration::from_millis(20)).await;
+        self.rst.set_high();
+
+        // Registers aren't ready for 120ms after reset is released
+        Timer::after(Duration::from_millis(120)).await;
+        self.state = LcdState::Uninit;
+    }
+
+    /// Reset the controller over SPI.
+    ///
+    /// Useful on boards where the RST line isn't wired up, or when it
+    /// might be glitched along with the rest of the panel.
+    pub async fn soft_reset(&mut self) -> Result<(), embassy_rp::spi::Error> {
+        self.state = LcdState::Uninit;
+        self.command(&[GC9A01A_SWRESET]).await?;
+        Timer::after(Duration::from_millis(120)).await;
+        Ok(())
+    }
+
+    /// Send the init sequence, then reapply the orientation.
+    ///
+    /// Does nothing unless the controller is `Uninit`.
+    pub async fn init(&mut self) -> Result<(), embassy_rp::spi::Error> {
+        if self.state != LcdState::Uninit {
+            return Ok(());
+        }
+
+        for c in INIT_SEQ {
+            match c {
+                InitOp::Cmd(c) => {
+                    // command
+                    self.command(&[c.cmd]).await?;
+                    Timer::after(Duration::from_micros(10)).await;
+
+                    // data
+                    self.data(c.data).await?;
+                    Timer::after(Duration::from_micros(10)).await;
+                }
+                InitOp::Delay(ms) => {
+                    Timer::after(Duration::from_millis(ms.into())).await;
+                }
+            }
+        }
+
+        // INIT_SEQ always sets the default MADCTL, put back whatever was chosen
+        self.set_orientation(self.orientation).await?;
+
+        self.state = LcdState::On;
+        Ok(())
+    }
+
+    /// Reset and initialize the controller from whatever state it's in.
+    ///
+    /// The backlight is off while this runs and restored afterwards.
+    /// The contents of the display are lost.
+    pub async fn reinit(&mut self) -> Result<(), embassy_rp::spi::Error> {
+        let duty = self.backlight.duty();
+        self.backlight.set_duty(0);
+
+        self.hard_reset().await;
+        self.soft_reset().await?;
+        self.init().await?;
+
+        self.backlight.set_duty(duty);
+        Ok(())
+    }
+
+    /// Turn off the backlight, then the panel, then put the controller to sleep.
+    pub async fn sleep(&mut self) -> Result<(), embassy_rp::spi::Error> {
+        if !matches!(self.state, LcdState::On | LcdState::Dimmed) {
+            return Ok(());
+        }
+
+        self.backlight.set_duty(0);
+        self.command(&[GC9A01A_DISPOFF]).await?;
+        self.command(&[GC9A01A_SLPIN]).await?;
+
+        // The controller needs 5ms before it accepts the next command
+        Timer::after(Duration::from_millis(5)).await;
+        self.state = LcdState::Asleep;
+        Ok(())
+    }
+
+    /// Bring the controller out of sleep and turn the panel back on.
+    ///
+    /// The backlight is left off, it's up to the caller to bring it back.
+    pub async fn wake(&mut self) -> Result<(), embassy_rp::spi::Error> {
+        if self.state != LcdState::Asleep {
+            return Ok(());
+        }
+
+        self.command(&[GC9A01A_SLPOUT]).await?;
+        Timer::after(Duration::from_millis(120)).await;
+        self.command(&[GC9A01A_DISPON]).await?;
+        Timer::after(Duration::from_millis(20)).await;
+        self.state = LcdState::On;
+        Ok(())
+    }
+
     pub async fn command(&mut self, cmd: &[u8]) -> Result<(), embassy_rp::spi::Error> {
         // command
         self.cs.set_low();
@@ -129,20 +381,47 @@ impl LcdPins {
         start_y: u8,
         end_y: u8,
         data: &[u8],
+    ) -> Result<(), embassy_rp::spi::Error> {
+        self.start_write(start_x, end_x, start_y, end_y).await?;
+        let res = self.write_pixels(data).await;
+        self.end_write();
+        res
+    }
+
+    /// Set the window and start a memory write.
+    ///
+    /// Pixels are then sent with [LcdPins::write_pixels], filling the window
+    /// left to right, top to bottom, and the write is finished with
+    /// [LcdPins::end_write]. This lets callers stream a window in pieces.
+    pub async fn start_write(
+        &mut self,
+        start_x: u8,
+        end_x: u8,
+        start_y: u8,
+        end_y: u8,
     ) -> Result<(), embassy_rp::spi::Error> {
         self.command(&[GC9A01A_CASET]).await?;
         self.data(&[0x00, start_x, 0x00, end_x - 1]).await?;
         self.command(&[GC9A01A_PASET]).await?;
         self.data(&[0x00, start_y, 0x00, end_y - 1]).await?;
         self.command(&[GC9A01A_RAMWR]).await?;
+        self.shadow.set_window(start_x, end_x, start_y, end_y);
 
         self.cs.set_low();
         self.dc.set_high();
 
-        self.spi.write(data).await?;
+        Ok(())
+    }
 
-        self.cs.set_high();
+    /// Send big-endian RGB565 pixels, after [LcdPins::start_write]
+    ///
+    /// All pixel data must go through here, so the shadow copy stays in sync.
+    pub async fn write_pixels(&mut self, data: &[u8]) -> Result<(), embassy_rp::spi::Error> {
+        self.shadow.write(data);
+        self.spi.write(data).await
+    }
 
-        Ok(())
+    pub fn end_write(&mut self) {
+        self.cs.set_high();
     }
 }
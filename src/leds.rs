@@ -1,70 +1,322 @@
-use embassy_rp::{pwm, peripherals::{PWM_CH3, PWM_CH5, PWM_CH0, PWM_CH7}};
+//! The four PWM LEDs
+//!
+//! The LEDs are owned by the [run] task, which plays an [Effect] on each of
+//! them. Effects are started by sending a [LedCmd] to [LED_CMDS], and keep
+//! running on their own while the REPL does other things.
+//!
+//! Brightness is a perceptual level, 0..=255, mapped through the same CIE
+//! curve as the backlight so equal steps look equal, and then scaled by a
+//! master brightness shared by all four LEDs.
+//!
+//! Each LED keeps its own [PwmSettings], so the frequency, resolution and
+//! polarity can suit whatever is wired to it.
 
-pub struct Leds {
-    pub led_1: pwm::Pwm<'static, PWM_CH3>,
-    pub led_2: pwm::Pwm<'static, PWM_CH5>,
-    pub led_3: pwm::Pwm<'static, PWM_CH0>,
-    pub led_4: pwm::Pwm<'static, PWM_CH7>,
+use embassy_futures::select::{select, Either};
+use embassy_rp::pwm;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::{
+    board,
+    fmath::{cos, perceptual_fine, sin},
+};
+
+pub const NUM_LEDS: usize = 4;
+
+/// How often running effects are updated
+const FRAME: Duration = Duration::from_millis(10);
+
+/// Commands waiting for [run]
+pub static LED_CMDS: Channel<ThreadModeRawMutex, LedCmd, 16> = Channel::new();
+
+/// Something for one LED to do. Brightness is a perceptual level 0..=255,
+/// and times are in milliseconds.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Stay at one brightness
+    Set(u8),
+    /// Fade from the current brightness to `target`, then stay there
+    Fade { target: u8, ms: u32 },
+    /// Smoothly up to `peak` and back to off, over and over
+    Breathe { peak: u8, period: u32 },
+    /// On at `peak` for the first half of each period, off for the rest
+    Blink { peak: u8, period: u32 },
+    /// A strong beat, a weaker one, then a rest, over and over
+    Heartbeat { peak: u8, period: u32 },
+    /// Up to `peak` and back to the current brightness, once
+    Pulse { peak: u8, ms: u32 },
 }
 
-impl Leds {
-    pub fn set_led(&mut self, idx: u8, val: u16) -> Result<(), ()> {
-        let mut config = embassy_rp::pwm::Config::default();
-        config.top = u16::MAX;
-        // config.compare_b = data;
-        // config.enable = true;
-
-        // forth.host_ctxt.lcd.backlight.set_config(&config);
-        // * 22 - (EXT) LED1 - PWM3A
-        // * 27 - (EXT) LED2 - PWM5B
-        // * 00 - (EXT) LED3 - PWM0A
-        // * 14 - (EXT) LED4 - PWM7A
-        match (idx, val) {
-            (0, 0) => {
-                // * 22 - (EXT) LED1 - PWM3A
-                config.enable = false;
-                self.led_1.set_config(&config);
+#[derive(Clone, Copy)]
+pub enum LedCmd {
+    /// Start an effect on LED `idx`, replacing whatever it was doing
+    Play { idx: u8, effect: Effect },
+    /// Scale every LED by this level, 0..=255
+    Master(u8),
+    /// Change how LED `idx`'s PWM runs
+    Pwm { idx: u8, settings: PwmSettings },
+}
+
+/// A level 0..=255 with 8 more bits, so slow fades don't visibly step
+fn fine(level: u8) -> i64 {
+    (level as i64) << 8
+}
+
+/// An effect playing on one LED
+#[derive(Clone, Copy)]
+struct Playing {
+    /// `None` once the LED is just holding `level`
+    effect: Option<Effect>,
+    start: Instant,
+    /// Fine level when the effect started
+    from: u16,
+    /// Fine level right now
+    level: u16,
+}
+
+impl Playing {
+    fn is_animated(&self) -> bool {
+        self.effect.is_some()
+    }
+
+    /// Fine level at `now`
+    fn step(&mut self, now: Instant) -> u16 {
+        let Some(effect) = self.effect else {
+            return self.level;
+        };
+
+        let t = (now - self.start).as_millis();
+        let from = self.from as i64;
+        // A half sine bump, 0 to 32767 and back over `len`, starting at `at`
+        let bump = |t: u64, at: u64, len: u64| -> i64 {
+            if t < at || t >= at + len {
+                return 0;
             }
-            (1, 0) => {
-                // * 27 - (EXT) LED2 - PWM5B
-                config.enable = false;
-                self.led_2.set_config(&config);
+            sin(((t - at) * 32768 / len) as u16) as i64
+        };
+
+        let level = match effect {
+            Effect::Set(level) => {
+                self.effect = None;
+                fine(level)
             }
-            (2, 0) => {
-                // * 00 - (EXT) LED3 - PWM0A
-                config.enable = false;
-                self.led_3.set_config(&config);
+            Effect::Fade { target, ms } => {
+                let ms = ms as u64;
+                if t >= ms {
+                    self.effect = None;
+                    fine(target)
+                } else {
+                    from + (fine(target) - from) * t as i64 / ms as i64
+                }
             }
-            (3, 0) => {
-                // * 14 - (EXT) LED4 - PWM7A
-                config.enable = false;
-                self.led_4.set_config(&config);
+            Effect::Breathe { peak, period } => {
+                let period = (period as u64).max(1);
+                let angle = (t % period * 65536 / period) as u16;
+                // Starts off, where cos is at its top
+                fine(peak) * (32767 - cos(angle) as i64) / (2 * 32767)
             }
-            (0, n) => {
-                // * 22 - (EXT) LED1 - PWM3A
-                config.enable = true;
-                config.compare_a = n;
-                self.led_1.set_config(&config);
+            Effect::Blink { peak, period } => {
+                let period = (period as u64).max(2);
+                if t % period < period / 2 {
+                    fine(peak)
+                } else {
+                    0
+                }
             }
-            (1, n) => {
-                // * 27 - (EXT) LED2 - PWM5B
-                config.enable = true;
-                config.compare_b = n;
-                self.led_2.set_config(&config);
+            Effect::Heartbeat { peak, period } => {
+                let period = (period as u64).max(1);
+                let t = t % period;
+                let len = (period * 15 / 100).max(1);
+                let beats = bump(t, 0, len) + bump(t, period * 25 / 100, len) * 3 / 5;
+                fine(peak) * beats / 32767
+            }
+            Effect::Pulse { peak, ms } => {
+                let ms = (ms as u64).max(1);
+                if t >= ms {
+                    self.effect = None;
+                    from
+                } else {
+                    from + (fine(peak) - from) * bump(t, 0, ms) / 32767
+                }
+            }
+        };
+        level.clamp(0, u16::MAX as i64) as u16
+    }
+}
+
+#[embassy_executor::task]
+pub async fn run(mut leds: Leds) {
+    let idle = Playing {
+        effect: None,
+        start: Instant::now(),
+        from: 0,
+        level: 0,
+    };
+    let mut playing = [idle; NUM_LEDS];
+    for idx in 0..NUM_LEDS {
+        leds.set_level(idx as u8, 0).ok();
+    }
+
+    loop {
+        // Only wake up for frames while something is moving
+        let cmd = if playing.iter().any(Playing::is_animated) {
+            match select(LED_CMDS.receive(), Timer::after(FRAME)).await {
+                Either::First(cmd) => Some(cmd),
+                Either::Second(()) => None,
             }
-            (2, n) => {
-                // * 00 - (EXT) LED3 - PWM0A
-                config.enable = true;
-                config.compare_a = n;
-                self.led_3.set_config(&config);
+        } else {
+            Some(LED_CMDS.receive().await)
+        };
+
+        let now = Instant::now();
+        let mut refresh = false;
+        let mut next = cmd;
+        while let Some(cmd) = next {
+            match cmd {
+                LedCmd::Play { idx, effect } => {
+                    if let Some(led) = playing.get_mut(idx as usize) {
+                        *led = Playing {
+                            effect: Some(effect),
+                            start: now,
+                            from: led.level,
+                            level: led.level,
+                        };
+                    }
+                }
+                LedCmd::Master(master) => {
+                    leds.master = master;
+                    refresh = true;
+                }
+                LedCmd::Pwm { idx, settings } => {
+                    leds.configure(idx, settings).ok();
+                }
             }
-            (3, n) => {
-                // * 14 - (EXT) LED4 - PWM7A
-                config.enable = true;
-                config.compare_a = n;
-                self.led_4.set_config(&config);
+            next = LED_CMDS.try_receive().ok();
+        }
+
+        for (idx, led) in playing.iter_mut().enumerate() {
+            let level = led.step(now);
+            if refresh || level != led.level {
+                leds.set_level_fine(idx as u8, level).ok();
+                led.level = level;
             }
-            _ => return Err(()),
+        }
+    }
+}
+
+/// How one LED's PWM slice runs
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PwmSettings {
+    /// Frequency in Hz, as close as the clock divider gets
+    pub freq: u32,
+    /// The counter wraps after `top`, giving `top + 1` steps of brightness
+    pub top: u16,
+    /// Count up then back down, at half the frequency
+    pub phase_correct: bool,
+    /// For LEDs wired active-low
+    pub invert: bool,
+}
+
+impl PwmSettings {
+    /// About what an undivided 16-bit counter gives at 125MHz
+    pub const DEFAULT: Self = Self {
+        freq: 1_900,
+        top: u16::MAX,
+        phase_correct: false,
+        invert: false,
+    };
+
+    /// The clock divider, in 1/16ths, clamped to what the hardware can do
+    fn divider(&self) -> u16 {
+        let sys = embassy_rp::clocks::clk_sys_freq() as u64;
+        let passes = if self.phase_correct { 2 } else { 1 };
+        let per_sec = (self.freq.max(1) as u64) * (self.top as u64 + 1) * passes;
+        (sys * 16 / per_sec).clamp(16, 255 * 16) as u16
+    }
+}
+
+pub struct Leds {
+    /// `None` when its slice is driving the buzzer instead
+    led_1: Option<pwm::Pwm<'static, board::Led1Pwm>>,
+    led_2: pwm::Pwm<'static, board::Led2Pwm>,
+    led_3: pwm::Pwm<'static, board::Led3Pwm>,
+    led_4: pwm::Pwm<'static, board::Led4Pwm>,
+    /// What each slice is running, kept so a new duty only changes the
+    /// compare value
+    configs: [pwm::Config; NUM_LEDS],
+    /// The last raw duty of each LED, 0..=65535
+    duty: [u16; NUM_LEDS],
+    /// Scales the brightness of every LED, 0..=255
+    pub master: u8,
+}
+
+impl Leds {
+    pub fn new(
+        led_1: Option<pwm::Pwm<'static, board::Led1Pwm>>,
+        led_2: pwm::Pwm<'static, board::Led2Pwm>,
+        led_3: pwm::Pwm<'static, board::Led3Pwm>,
+        led_4: pwm::Pwm<'static, board::Led4Pwm>,
+    ) -> Self {
+        let mut leds = Self {
+            led_1,
+            led_2,
+            led_3,
+            led_4,
+            configs: core::array::from_fn(|_| pwm::Config::default()),
+            duty: [0; NUM_LEDS],
+            master: u8::MAX,
+        };
+        for idx in 0..NUM_LEDS {
+            leds.configure(idx as u8, PwmSettings::DEFAULT).ok();
+        }
+        leds
+    }
+
+    /// Change the frequency, resolution and polarity of one LED, keeping
+    /// its brightness
+    pub fn configure(&mut self, idx: u8, settings: PwmSettings) -> Result<(), ()> {
+        let output = *board::LED_OUTPUTS.get(idx as usize).ok_or(())?;
+        let mut config = pwm::Config::default();
+        config.top = settings.top;
+        config.phase_correct = settings.phase_correct;
+        config.divider = fixed::FixedU16::from_bits(settings.divider());
+        output.set_invert(&mut config, settings.invert);
+        config.enable = true;
+
+        self.configs[idx as usize] = config;
+        self.set_led(idx, self.duty[idx as usize])
+    }
+
+    /// Set a perceptual brightness, 0..=255, scaled by `master`
+    pub fn set_level(&mut self, idx: u8, level: u8) -> Result<(), ()> {
+        self.set_level_fine(idx, fine(level) as u16)
+    }
+
+    /// Like [Leds::set_level], with a 16-bit level for smooth fades
+    pub fn set_level_fine(&mut self, idx: u8, level: u16) -> Result<(), ()> {
+        let scaled = level as u32 * self.master as u32 / u8::MAX as u32;
+        self.set_led(idx, perceptual_fine(scaled as u16))
+    }
+
+    /// Set a raw PWM duty, 0..=65535, whatever the LED's `top`
+    pub fn set_led(&mut self, idx: u8, val: u16) -> Result<(), ()> {
+        let idx = idx as usize;
+        let output = *board::LED_OUTPUTS.get(idx).ok_or(())?;
+        let config = &mut self.configs[idx];
+
+        // Full duty is a compare past `top`, so the output never drops
+        let steps = config.top as u32 + 1;
+        let compare = (val as u32 * steps / u16::MAX as u32).min(u16::MAX as u32);
+        output.set_compare(config, compare as u16);
+        self.duty[idx] = val;
+
+        // Only the compare value changed, the counter keeps running
+        let config = &self.configs[idx];
+        match idx {
+            0 => self.led_1.as_mut().ok_or(())?.set_config(config),
+            1 => self.led_2.set_config(config),
+            2 => self.led_3.set_config(config),
+            _ => self.led_4.set_config(config),
         }
 
         Ok(())
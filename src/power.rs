@@ -0,0 +1,98 @@
+//! Display power management
+//!
+//! The display is dimmed after `dim_after` without any activity, and put to
+//! sleep after `sleep_after`. Button presses wake it back up. Input over USB
+//! counts as activity too, but won't wake a sleeping display, so typing
+//! `lcd-sleep` and then more commands doesn't immediately undo itself.
+//!
+//! The state itself lives in [LcdState], this only decides when to move
+//! between states.
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
+use embassy_time::{Duration, Instant};
+
+use crate::lcd::{LcdPins, LcdState};
+
+/// Signalled by the buttons task on any change
+pub static BUTTON_ACTIVITY: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+const FADE_TIME: Duration = Duration::from_millis(400);
+
+pub struct Power {
+    /// Brightness used while dimmed, 0..=255
+    pub dim_level: u8,
+    /// `None` never dims
+    pub dim_after: Option<Duration>,
+    /// `None` never sleeps
+    pub sleep_after: Option<Duration>,
+    /// Brightness to return to when leaving `Dimmed` or `Asleep`
+    restore_level: u8,
+    last_activity: Instant,
+}
+
+impl Power {
+    pub fn new() -> Self {
+        Self {
+            dim_level: 16,
+            dim_after: Some(Duration::from_secs(30)),
+            sleep_after: Some(Duration::from_secs(90)),
+            restore_level: 0,
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// Note some user activity. Only a button press wakes a sleeping display.
+    pub async fn activity(&mut self, lcd: &mut LcdPins, is_button: bool) {
+        self.last_activity = Instant::now();
+        match lcd.state {
+            LcdState::Dimmed => {
+                lcd.backlight.fade_to(self.restore_level, FADE_TIME).await;
+                lcd.state = LcdState::On;
+            }
+            LcdState::Asleep if is_button => self.wake(lcd).await,
+            _ => {}
+        }
+    }
+
+    /// Check the inactivity timeouts, dimming or sleeping as needed
+    pub async fn tick(&mut self, lcd: &mut LcdPins) {
+        let idle = Instant::now() - self.last_activity;
+        let expired = |limit: Option<Duration>| limit.map(|l| idle >= l).unwrap_or(false);
+
+        match lcd.state {
+            LcdState::On | LcdState::Dimmed if expired(self.sleep_after) => {
+                self.sleep(lcd).await;
+            }
+            LcdState::On if expired(self.dim_after) => {
+                self.restore_level = lcd.backlight.level();
+                let target = self.dim_level.min(self.restore_level);
+                lcd.backlight.fade_to(target, FADE_TIME).await;
+                lcd.state = LcdState::Dimmed;
+            }
+            _ => {}
+        }
+    }
+
+    pub async fn sleep(&mut self, lcd: &mut LcdPins) {
+        match lcd.state {
+            LcdState::On => self.restore_level = lcd.backlight.level(),
+            LcdState::Dimmed => {}
+            LcdState::Uninit | LcdState::Asleep => return,
+        }
+        lcd.backlight.fade_to(0, FADE_TIME).await;
+        lcd.sleep().await.ok();
+    }
+
+    pub async fn wake(&mut self, lcd: &mut LcdPins) {
+        self.last_activity = Instant::now();
+        match lcd.state {
+            LcdState::Dimmed => {}
+            LcdState::Asleep => {
+                lcd.wake().await.ok();
+            }
+            LcdState::Uninit | LcdState::On => return,
+        }
+        lcd.backlight.fade_to(self.restore_level, FADE_TIME).await;
+        lcd.state = LcdState::On;
+    }
+}
@@ -0,0 +1,178 @@
+//! Ring tones, in RTTTL
+//!
+//! A tune looks like `name:d=4,o=5,b=120:8c,8e,g,2c6`. The name is ignored,
+//! then come the default duration, octave and beats per minute, then the
+//! notes. Each note is an optional duration (1 is a whole note, 4 a
+//! quarter), a letter `a` to `g` with an optional `#`, or `p` for a rest, an
+//! optional octave, and a `.` to make it half as long again.
+//!
+//! The name and defaults can be left off, for a compact list of notes using
+//! the usual defaults of `d=4,o=6,b=63`.
+
+/// Most notes in one tune
+pub const MAX_NOTES: usize = 128;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    /// 0 for a rest
+    pub hz: u16,
+    pub ms: u16,
+}
+
+pub type Melody = heapless::Vec<Note, MAX_NOTES>;
+
+/// C8 to B8, every other octave is these halved
+const OCTAVE_8: [u16; 12] = [
+    4186, 4435, 4699, 4978, 5274, 5588, 5920, 6272, 6645, 7040, 7459, 7902,
+];
+
+struct Defaults {
+    duration: u32,
+    octave: u32,
+    bpm: u32,
+}
+
+/// Parse a whole tune
+pub fn parse(text: &[u8]) -> Result<Melody, ()> {
+    let mut sections = text.rsplitn(3, |b| *b == b':');
+    let notes = sections.next().ok_or(())?;
+    let defaults = match sections.next() {
+        Some(defaults) => parse_defaults(defaults)?,
+        None => Defaults {
+            duration: 4,
+            octave: 6,
+            bpm: 63,
+        },
+    };
+
+    let whole_ms = 4 * 60_000 / defaults.bpm.max(1);
+    let mut melody = Melody::new();
+    for note in notes.split(|b| *b == b',') {
+        let note = trim(note);
+        if note.is_empty() {
+            continue;
+        }
+        melody
+            .push(parse_note(note, &defaults, whole_ms)?)
+            .map_err(drop)?;
+    }
+    Ok(melody)
+}
+
+fn parse_defaults(text: &[u8]) -> Result<Defaults, ()> {
+    let mut defaults = Defaults {
+        duration: 4,
+        octave: 6,
+        bpm: 63,
+    };
+    for setting in text.split(|b| *b == b',') {
+        let setting = trim(setting);
+        if setting.is_empty() {
+            continue;
+        }
+        let (key, val) = match setting {
+            [key, b'=', val @ ..] => (key.to_ascii_lowercase(), number(trim(val))?),
+            _ => return Err(()),
+        };
+        match key {
+            b'd' => defaults.duration = val,
+            b'o' => defaults.octave = val,
+            b'b' => defaults.bpm = val,
+            _ => return Err(()),
+        }
+    }
+    Ok(defaults)
+}
+
+fn parse_note(mut text: &[u8], defaults: &Defaults, whole_ms: u32) -> Result<Note, ()> {
+    let digits = text.iter().take_while(|b| b.is_ascii_digit()).count();
+    let duration = if digits == 0 {
+        defaults.duration
+    } else {
+        number(&text[..digits])?
+    };
+    text = &text[digits..];
+
+    let (letter, rest) = text.split_first().ok_or(())?;
+    text = rest;
+    let mut semitone = match letter.to_ascii_lowercase() {
+        b'c' => Some(0),
+        b'd' => Some(2),
+        b'e' => Some(4),
+        b'f' => Some(5),
+        b'g' => Some(7),
+        b'a' => Some(9),
+        b'b' | b'h' => Some(11),
+        b'p' => None,
+        _ => return Err(()),
+    };
+    if let [b'#', rest @ ..] = text {
+        semitone = semitone.map(|s| s + 1);
+        text = rest;
+    }
+
+    // The dot is allowed before or after the octave
+    let mut dotted = false;
+    if let [b'.', rest @ ..] = text {
+        dotted = true;
+        text = rest;
+    }
+    let octave = match text {
+        [digit, rest @ ..] if digit.is_ascii_digit() => {
+            text = rest;
+            (digit - b'0') as u32
+        }
+        _ => defaults.octave,
+    };
+    if let [b'.', rest @ ..] = text {
+        dotted = true;
+        text = rest;
+    }
+    if !text.is_empty() || octave > 8 {
+        return Err(());
+    }
+
+    let mut ms = whole_ms / duration.max(1);
+    if dotted {
+        ms += ms / 2;
+    }
+    let hz = match semitone {
+        // b# is the next octave's c
+        Some(12) => OCTAVE_8[0] >> (7 - octave.min(7)),
+        Some(s) => OCTAVE_8[s] >> (8 - octave),
+        None => 0,
+    };
+    Ok(Note {
+        hz,
+        ms: ms.min(u16::MAX as u32) as u16,
+    })
+}
+
+fn number(text: &[u8]) -> Result<u32, ()> {
+    if text.is_empty() || text.len() > 6 {
+        return Err(());
+    }
+    text.iter().try_fold(0, |n, b| {
+        if b.is_ascii_digit() {
+            Ok(n * 10 + (b - b'0') as u32)
+        } else {
+            Err(())
+        }
+    })
+}
+
+fn trim(mut text: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = text {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        text = rest;
+    }
+    while let [rest @ .., last] = text {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        text = rest;
+    }
+    text
+}
@@ -0,0 +1,184 @@
+//! A copy of everything on the panel
+//!
+//! The panel is wired up TX-only, so there's no reading its memory back.
+//! Instead every pixel sent through [LcdPins::write_pixels] is also written
+//! here, following the window set by [LcdPins::start_write] the same way
+//! the controller does. This costs 115200 bytes of RAM, and makes things like
+//! `screenshot` possible.
+//!
+//! Pixels are kept in logical coordinates, the ones used for drawing, as
+//! big-endian RGB565.
+//!
+//! [LcdPins::write_pixels]: crate::lcd::LcdPins::write_pixels
+//! [LcdPins::start_write]: crate::lcd::LcdPins::start_write
+
+use core::cell::UnsafeCell;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::gc9a01a::registers::{MADCTL_MV, MADCTL_MX, MADCTL_MY};
+
+pub const WIDTH: usize = 240;
+pub const HEIGHT: usize = 240;
+pub const FB_BYTES: usize = WIDTH * HEIGHT * 2;
+
+struct ShadowBuf(UnsafeCell<[u8; FB_BYTES]>);
+
+unsafe impl Sync for ShadowBuf {}
+
+static SHADOW_BUF: ShadowBuf = ShadowBuf(UnsafeCell::new([0; FB_BYTES]));
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+pub struct Shadow {
+    buf: &'static mut [u8; FB_BYTES],
+    // The window, end exclusive
+    xs: usize,
+    xe: usize,
+    ys: usize,
+    ye: usize,
+    // The next pixel to be written
+    x: usize,
+    y: usize,
+    /// The first byte of a pixel split across two writes
+    half: Option<u8>,
+}
+
+impl Shadow {
+    /// Take the framebuffer, this only works once
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+
+        // SAFETY: Only handed out once, checked above
+        let buf = unsafe { &mut *SHADOW_BUF.0.get() };
+        Some(Self {
+            buf,
+            xs: 0,
+            xe: WIDTH,
+            ys: 0,
+            ye: HEIGHT,
+            x: 0,
+            y: 0,
+            half: None,
+        })
+    }
+
+    /// Start a new write at the top left of the window
+    pub fn set_window(&mut self, start_x: u8, end_x: u8, start_y: u8, end_y: u8) {
+        self.xs = (start_x as usize).min(WIDTH - 1);
+        self.xe = (end_x as usize).clamp(self.xs + 1, WIDTH);
+        self.ys = (start_y as usize).min(HEIGHT - 1);
+        self.ye = (end_y as usize).clamp(self.ys + 1, HEIGHT);
+        self.x = self.xs;
+        self.y = self.ys;
+        self.half = None;
+    }
+
+    /// Copy pixels in, wrapping back to the top of the window at the end
+    /// like the controller does
+    pub fn write(&mut self, mut data: &[u8]) {
+        if let Some(hi) = self.half.take() {
+            let Some((lo, rest)) = data.split_first() else {
+                self.half = Some(hi);
+                return;
+            };
+            self.write_run(&[hi, *lo]);
+            data = rest;
+        }
+
+        while data.len() >= 2 {
+            let px = ((self.xe - self.x) * 2).min(data.len() & !1);
+            let (run, rest) = data.split_at(px);
+            self.write_run(run);
+            data = rest;
+        }
+
+        if let Some(b) = data.first() {
+            self.half = Some(*b);
+        }
+    }
+
+    /// Write whole pixels that fit in the current row of the window
+    fn write_run(&mut self, run: &[u8]) {
+        let start = (self.y * WIDTH + self.x) * 2;
+        self.buf[start..][..run.len()].copy_from_slice(run);
+
+        self.x += run.len() / 2;
+        if self.x >= self.xe {
+            self.x = self.xs;
+            self.y += 1;
+            if self.y >= self.ye {
+                self.y = self.ys;
+            }
+        }
+    }
+
+    /// One row of pixels
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.buf[y * WIDTH * 2..][..WIDTH * 2]
+    }
+
+    /// Move the pixels so they still match the glass after the `MADCTL`
+    /// value changes from `from` to `to`.
+    ///
+    /// The controller maps a logical position to the glass by exchanging
+    /// rows and columns (`MV`), then flipping columns (`MX`) and rows (`MY`).
+    /// Going through glass coordinates, the old mapping is applied, and then
+    /// the new one undone.
+    pub fn reorient(&mut self, from: u8, to: u8) {
+        let bits = MADCTL_MV | MADCTL_MX | MADCTL_MY;
+        if (from & bits) == (to & bits) {
+            return;
+        }
+
+        if (from & MADCTL_MV) != 0 {
+            self.transpose();
+        }
+        if (from & MADCTL_MX) != 0 {
+            self.flip_x();
+        }
+        if (from & MADCTL_MY) != 0 {
+            self.flip_y();
+        }
+
+        if (to & MADCTL_MY) != 0 {
+            self.flip_y();
+        }
+        if (to & MADCTL_MX) != 0 {
+            self.flip_x();
+        }
+        if (to & MADCTL_MV) != 0 {
+            self.transpose();
+        }
+    }
+
+    fn swap_px(&mut self, a: usize, b: usize) {
+        self.buf.swap(a * 2, b * 2);
+        self.buf.swap(a * 2 + 1, b * 2 + 1);
+    }
+
+    fn transpose(&mut self) {
+        for y in 0..HEIGHT {
+            for x in (y + 1)..WIDTH {
+                self.swap_px(y * WIDTH + x, x * WIDTH + y);
+            }
+        }
+    }
+
+    fn flip_x(&mut self) {
+        for y in 0..HEIGHT {
+            for x in 0..(WIDTH / 2) {
+                self.swap_px(y * WIDTH + x, y * WIDTH + (WIDTH - 1 - x));
+            }
+        }
+    }
+
+    fn flip_y(&mut self) {
+        let row = WIDTH * 2;
+        for y in 0..(HEIGHT / 2) {
+            let (top, bottom) = self.buf.split_at_mut((HEIGHT - 1 - y) * row);
+            top[y * row..][..row].swap_with_slice(&mut bottom[..row]);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+//! WS2812 style strips
+//!
+//! Like the PWM LEDs, the strips are owned by the [run] task and driven by
+//! sending it a [StripCmd]. Pixels can be set one by one, or an [Effect]
+//! can animate a whole strip in the background while the REPL carries on.
+//! Colors are kept as set, with gamma and brightness applied on the way out,
+//! so changing either doesn't lose anything.
+//!
+//! The board lists its strips in [STRIPS], each on its own PIO0 state
+//! machine with its own length and pixel [Format]. On the original badge
+//! the strip shares the SPI0 port with the flash, so there are only strips
+//! with the `smartled` feature. Without any, the words fail instead of
+//! queueing up commands nobody reads.
+
+#[cfg(feature = "smartled")]
+use embassy_futures::select::{select, Either};
+#[cfg(feature = "smartled")]
+use embassy_rp::{peripherals::PIO0, pio::Common};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+#[cfg(feature = "smartled")]
+use embassy_time::{Duration, Instant, Timer};
+#[cfg(feature = "smartled")]
+use smart_leds::colors;
+use smart_leds::RGB8;
+
+use crate::board;
+#[cfg(feature = "smartled")]
+use crate::{
+    color::wheel,
+    fmath::cos,
+    ws2812::{brightness_one, gamma_one, Format, Ws2812},
+};
+
+/// How one strip is made up
+#[derive(Clone, Copy)]
+pub struct StripInfo {
+    /// Pixels on the strip
+    pub len: usize,
+    #[cfg(feature = "smartled")]
+    pub format: Format,
+}
+
+/// The board's strips, in state machine order
+pub const STRIPS: &[StripInfo] = board::SMARTLED_STRIPS;
+
+/// One strip per state machine
+#[cfg(feature = "smartled")]
+pub const MAX_STRIPS: usize = 4;
+
+/// Pixels on the longest strip
+#[cfg(feature = "smartled")]
+pub const LEN: usize = {
+    let mut len = 0;
+    let mut i = 0;
+    while i < STRIPS.len() {
+        if STRIPS[i].len > len {
+            len = STRIPS[i].len;
+        }
+        i += 1;
+    }
+    len
+};
+
+/// The most frames per second effects will run at
+#[cfg(feature = "smartled")]
+pub const MAX_FPS: u32 = 50;
+
+/// The brightest the strip is ever driven, so a white strip can't pull more
+/// than the port can supply
+#[cfg(feature = "smartled")]
+pub const MAX_BRIGHTNESS: u8 = 128;
+
+/// Commands waiting for [run]
+pub static SMARTLED_CMDS: Channel<ThreadModeRawMutex, StripCmd, 16> = Channel::new();
+
+/// Something for the whole strip to do. Times are in milliseconds.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// The color wheel spread along the strip, turning once per `period`
+    Rainbow { period: u32 },
+    /// Every third pixel lit, stepping along every `step`
+    Chase { color: RGB8, step: u32 },
+    /// Fill the strip with `color` one pixel at a time, then hold
+    Wipe { color: RGB8, ms: u32 },
+    /// Pixels flash up at random and fade away, `rate` in 256 each frame
+    Twinkle { color: RGB8, rate: u8 },
+    /// Flames rising from the start of the strip. More `cooling` gives
+    /// shorter flames, more `sparking` a busier fire.
+    Fire { cooling: u8, sparking: u8 },
+    /// A bright head with a fading tail, going round once per `period`
+    Comet { color: RGB8, period: u32 },
+    /// The whole strip smoothly up to `color` and back, over and over
+    Breathe { color: RGB8, period: u32 },
+}
+
+// Only read by [run], which needs the `smartled` feature
+#[cfg_attr(not(feature = "smartled"), allow(dead_code))]
+#[derive(Clone, Copy)]
+pub enum SmartLedCmd {
+    /// Set one pixel, stopping any effect
+    Set { idx: u16, color: RGB8 },
+    /// Set every pixel, stopping any effect
+    Fill(RGB8),
+    /// Start an effect, replacing whatever was running
+    Play(Effect),
+    /// Stop the effect, holding the last frame
+    Stop,
+    /// Whether to gamma correct colors
+    Gamma(bool),
+    /// Scale every pixel, 0..=255, where 255 is [MAX_BRIGHTNESS]
+    Brightness(u8),
+    /// Frames per second for effects on every strip, up to [MAX_FPS]
+    Fps(u32),
+}
+
+/// A command for one strip, an index into [STRIPS]
+#[cfg_attr(not(feature = "smartled"), allow(dead_code))]
+#[derive(Clone, Copy)]
+pub struct StripCmd {
+    pub strip: u8,
+    pub cmd: SmartLedCmd,
+}
+
+/// A strip on any of the state machines. Made from a [Ws2812] by
+/// [Strips::add].
+#[cfg(feature = "smartled")]
+pub enum Driver {
+    Sm0(Ws2812<'static, PIO0, 0, LEN>),
+    Sm1(Ws2812<'static, PIO0, 1, LEN>),
+    Sm2(Ws2812<'static, PIO0, 2, LEN>),
+    Sm3(Ws2812<'static, PIO0, 3, LEN>),
+}
+
+#[cfg(feature = "smartled")]
+impl Driver {
+    async fn write(&mut self, colors: &[RGB8]) {
+        match self {
+            Driver::Sm0(ws2812) => ws2812.write(colors).await,
+            Driver::Sm1(ws2812) => ws2812.write(colors).await,
+            Driver::Sm2(ws2812) => ws2812.write(colors).await,
+            Driver::Sm3(ws2812) => ws2812.write(colors).await,
+        }
+    }
+}
+
+#[cfg(feature = "smartled")]
+impl From<Ws2812<'static, PIO0, 0, LEN>> for Driver {
+    fn from(ws2812: Ws2812<'static, PIO0, 0, LEN>) -> Self {
+        Driver::Sm0(ws2812)
+    }
+}
+
+#[cfg(feature = "smartled")]
+impl From<Ws2812<'static, PIO0, 1, LEN>> for Driver {
+    fn from(ws2812: Ws2812<'static, PIO0, 1, LEN>) -> Self {
+        Driver::Sm1(ws2812)
+    }
+}
+
+#[cfg(feature = "smartled")]
+impl From<Ws2812<'static, PIO0, 2, LEN>> for Driver {
+    fn from(ws2812: Ws2812<'static, PIO0, 2, LEN>) -> Self {
+        Driver::Sm2(ws2812)
+    }
+}
+
+#[cfg(feature = "smartled")]
+impl From<Ws2812<'static, PIO0, 3, LEN>> for Driver {
+    fn from(ws2812: Ws2812<'static, PIO0, 3, LEN>) -> Self {
+        Driver::Sm3(ws2812)
+    }
+}
+
+/// The strips and the PIO block running them
+#[cfg(feature = "smartled")]
+pub struct Strips {
+    /// Kept so the PIO isn't torn down under the state machines
+    _pio: Common<'static, PIO0>,
+    drivers: heapless::Vec<Driver, MAX_STRIPS>,
+}
+
+#[cfg(feature = "smartled")]
+impl Strips {
+    pub fn new(pio: Common<'static, PIO0>) -> Self {
+        Self {
+            _pio: pio,
+            drivers: heapless::Vec::new(),
+        }
+    }
+
+    /// Add the next strip in [STRIPS], on whichever state machine
+    pub fn add(&mut self, strip: impl Into<Driver>) {
+        assert!(self.drivers.len() < STRIPS.len());
+        self.drivers.push(strip.into()).ok();
+    }
+}
+
+/// A small xorshift, plenty for twinkles and flames
+#[cfg(feature = "smartled")]
+struct Rng(u32);
+
+#[cfg(feature = "smartled")]
+impl Rng {
+    fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// 0..n
+    fn below(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            0
+        } else {
+            self.next() % n
+        }
+    }
+}
+
+/// The running effect and whatever it remembers between frames
+#[cfg(feature = "smartled")]
+struct Playing {
+    effect: Effect,
+    start: Instant,
+    /// How hot each pixel is, for [Effect::Fire]
+    heat: [u8; LEN],
+}
+
+#[cfg(feature = "smartled")]
+impl Playing {
+    fn new(effect: Effect, now: Instant) -> Self {
+        Self {
+            effect,
+            start: now,
+            heat: [0; LEN],
+        }
+    }
+
+    /// Draw the frame at `now` into `pixels`. Returns false once the effect
+    /// has finished.
+    fn step(&mut self, now: Instant, pixels: &mut [RGB8], rng: &mut Rng) -> bool {
+        let t = (now - self.start).as_millis();
+        let count = pixels.len();
+        let len = count as u64;
+        // Several effects divide by the length
+        if count == 0 {
+            return false;
+        }
+
+        match self.effect {
+            Effect::Rainbow { period } => {
+                let period = (period as u64).max(1);
+                let turn = t % period * 256 / period;
+                for (i, px) in pixels.iter_mut().enumerate() {
+                    *px = wheel((i as u64 * 256 / len + turn) as u8);
+                }
+            }
+            Effect::Chase { color, step } => {
+                let offset = (t / (step as u64).max(1) % 3) as usize;
+                for (i, px) in pixels.iter_mut().enumerate() {
+                    *px = if i % 3 == offset {
+                        color
+                    } else {
+                        colors::BLACK
+                    };
+                }
+            }
+            Effect::Wipe { color, ms } => {
+                let ms = (ms as u64).max(1);
+                let lit = ((t * len / ms) as usize + 1).min(count);
+                pixels[..lit].fill(color);
+                return lit < count;
+            }
+            Effect::Twinkle { color, rate } => {
+                for px in pixels.iter_mut() {
+                    *px = brightness_one(*px, 224);
+                    if rng.below(256) < rate as u32 {
+                        *px = color;
+                    }
+                }
+            }
+            Effect::Fire { cooling, sparking } => {
+                // Everything cools a little
+                let heats = &mut self.heat[..count];
+                let most = cooling as u32 * 10 / count as u32 + 2;
+                for heat in heats.iter_mut() {
+                    *heat = heat.saturating_sub(rng.below(most + 1).min(255) as u8);
+                }
+                // Heat drifts up the strip
+                for i in (2..count).rev() {
+                    let below = heats[i - 1] as u16 + 2 * heats[i - 2] as u16;
+                    heats[i] = (below / 3) as u8;
+                }
+                // Sometimes a new spark near the bottom
+                if rng.below(256) < sparking as u32 {
+                    let at = rng.below(count.min(7) as u32) as usize;
+                    let spark = 160 + rng.below(96) as u8;
+                    heats[at] = heats[at].saturating_add(spark);
+                }
+                for (px, heat) in pixels.iter_mut().zip(heats.iter()) {
+                    *px = heat_color(*heat);
+                }
+            }
+            Effect::Comet { color, period } => {
+                let period = (period as u64).max(1);
+                // Where the head is, in 1/256ths of a pixel
+                let head = t % period * len * 256 / period;
+                let tail = (len * 256 / 3).max(256);
+                for (i, px) in pixels.iter_mut().enumerate() {
+                    let at = i as u64 * 256;
+                    let behind = (head + len * 256 - at) % (len * 256);
+                    *px = if behind < tail {
+                        brightness_one(color, (255 - behind * 255 / tail) as u8)
+                    } else {
+                        colors::BLACK
+                    };
+                }
+            }
+            Effect::Breathe { color, period } => {
+                let period = (period as u64).max(1);
+                let angle = (t % period * 65536 / period) as u16;
+                // Starts off, where cos is at its top
+                let level = (32767 - cos(angle)) * 255 / (2 * 32767);
+                pixels.fill(brightness_one(color, level as u8));
+            }
+        }
+        true
+    }
+}
+
+/// Black through red and yellow to white as `heat` goes up
+#[cfg(feature = "smartled")]
+fn heat_color(heat: u8) -> RGB8 {
+    let t = (heat as u16 * 191 / 255) as u8;
+    let ramp = (t & 0x3F) << 2;
+    match t {
+        0x80.. => RGB8::new(255, 255, ramp),
+        0x40.. => RGB8::new(255, ramp, 0),
+        _ => RGB8::new(ramp, 0, 0),
+    }
+}
+
+/// What one strip is showing
+#[cfg(feature = "smartled")]
+struct Shown {
+    /// As set, before gamma and brightness
+    pixels: [RGB8; LEN],
+    len: usize,
+    playing: Option<Playing>,
+    gamma: bool,
+    brightness: u8,
+}
+
+#[cfg(feature = "smartled")]
+impl Shown {
+    fn pixels(&mut self) -> &mut [RGB8] {
+        &mut self.pixels[..self.len]
+    }
+
+    fn apply(&mut self, cmd: SmartLedCmd, now: Instant) {
+        match cmd {
+            SmartLedCmd::Set { idx, color } => {
+                self.playing = None;
+                if let Some(px) = self.pixels().get_mut(idx as usize) {
+                    *px = color;
+                }
+            }
+            SmartLedCmd::Fill(color) => {
+                self.playing = None;
+                self.pixels().fill(color);
+            }
+            SmartLedCmd::Play(effect) => self.playing = Some(Playing::new(effect, now)),
+            SmartLedCmd::Stop => self.playing = None,
+            SmartLedCmd::Gamma(on) => self.gamma = on,
+            SmartLedCmd::Brightness(level) => {
+                self.brightness = (level as u16 * MAX_BRIGHTNESS as u16 / 255) as u8
+            }
+            // Handled by [run], for every strip
+            SmartLedCmd::Fps(_) => {}
+        }
+    }
+
+    async fn show(&mut self, driver: &mut Driver, now: Instant, rng: &mut Rng) {
+        if let Some(effect) = self.playing.as_mut() {
+            if !effect.step(now, &mut self.pixels[..self.len], rng) {
+                self.playing = None;
+            }
+        }
+
+        let mut out = self.pixels;
+        for px in out[..self.len].iter_mut() {
+            if self.gamma {
+                *px = gamma_one(*px);
+            }
+            *px = brightness_one(*px, self.brightness);
+        }
+        driver.write(&out[..self.len]).await;
+    }
+}
+
+#[cfg(feature = "smartled")]
+#[embassy_executor::task]
+pub async fn run(mut strips: Strips) {
+    let mut shown: heapless::Vec<Shown, MAX_STRIPS> = STRIPS
+        .iter()
+        .map(|info| Shown {
+            pixels: [colors::BLACK; LEN],
+            len: info.len,
+            playing: None,
+            gamma: true,
+            brightness: MAX_BRIGHTNESS,
+        })
+        .collect();
+    let mut frame = Duration::from_millis(1000 / MAX_FPS as u64);
+    let mut rng = Rng(Instant::now().as_ticks() as u32 | 1);
+
+    loop {
+        // Only wake up for frames while an effect is running
+        let animating = shown.iter().any(|s| s.playing.is_some());
+        let cmd = if animating {
+            match select(SMARTLED_CMDS.receive(), Timer::after(frame)).await {
+                Either::First(cmd) => Some(cmd),
+                Either::Second(()) => None,
+            }
+        } else {
+            Some(SMARTLED_CMDS.receive().await)
+        };
+
+        let now = Instant::now();
+        let mut changed = [false; MAX_STRIPS];
+        let mut next = cmd;
+        while let Some(StripCmd { strip, cmd }) = next {
+            if let SmartLedCmd::Fps(fps) = cmd {
+                frame = Duration::from_millis(1000 / fps.clamp(1, MAX_FPS) as u64);
+            } else if let Some(strip_shown) = shown.get_mut(strip as usize) {
+                strip_shown.apply(cmd, now);
+                changed[strip as usize] = true;
+            }
+            next = SMARTLED_CMDS.try_receive().ok();
+        }
+
+        let pairs = shown.iter_mut().zip(strips.drivers.iter_mut());
+        for (idx, (strip, driver)) in pairs.enumerate() {
+            if changed[idx] || strip.playing.is_some() {
+                strip.show(driver, now, &mut rng).await;
+            }
+        }
+    }
+}
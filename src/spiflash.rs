@@ -1,7 +1,9 @@
-use embassy_rp::{spi::{Spi, Async}, peripherals::SPI0, gpio::{Output, AnyPin, Input}};
+use embassy_rp::{spi::{Spi, Async}, gpio::{Output, AnyPin, Input}};
+
+use crate::board;
 
 pub struct SpiFlash {
-    pub spi: Spi<'static, SPI0, Async>,
+    pub spi: Spi<'static, board::FlashSpi, Async>,
     pub csn: Output<'static, AnyPin>,
     // For now, keep these floating
     pub io2: Input<'static, AnyPin>,
@@ -18,4 +20,19 @@ impl SpiFlash {
 
         [buf[1], buf[2], buf[3]]
     }
+
+    /// Read `buf.len()` bytes starting at `addr`
+    pub async fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), embassy_rp::spi::Error> {
+        let [_, a2, a1, a0] = addr.to_be_bytes();
+
+        self.csn.set_low();
+        let res = async {
+            self.spi.write(&[0x03, a2, a1, a0]).await?;
+            self.spi.read(buf).await
+        }
+        .await;
+        self.csn.set_high();
+
+        res
+    }
 }
@@ -0,0 +1,595 @@
+//! Sprites composited over a background
+//!
+//! A [Scene] holds a background and up to [MAX_SPRITES] sprites, each on a
+//! layer. Moving, showing or hiding a sprite only marks the areas it used to
+//! cover and now covers as dirty. [Scene::render] then redraws just those
+//! areas, building each row from the background up through the layers, so
+//! nothing flickers and nothing needs hand-tuned partial redraws.
+//!
+//! Sprite pixels are big-endian RGB565, like an [Image], and can live in the
+//! firmware or in the external flash, or be copied into the scene's own
+//! [SPRITE_RAM] bytes. An optional [Mask] makes parts of a sprite
+//! transparent, either 1 bit per pixel (MSB first, each row padded to a whole
+//! byte) or 8-bit alpha.
+
+use core::{cell::UnsafeCell, cmp::Reverse};
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::{
+    color::{blend, rgb565_to_rgb8},
+    image::{self, Image, ImageSource},
+    lcd::LcdPins,
+    spiflash::SpiFlash,
+};
+
+pub const MAX_SPRITES: usize = 16;
+
+/// Room for sprite pixels and masks copied in with [Scene::set_ram] and
+/// [Scene::set_mask_ram]
+pub const SPRITE_RAM: usize = 16 * 1024;
+
+struct RamBuf(UnsafeCell<[u8; SPRITE_RAM]>);
+
+unsafe impl Sync for RamBuf {}
+
+static RAM_BUF: RamBuf = RamBuf(UnsafeCell::new([0; SPRITE_RAM]));
+static RAM_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Take the memory for [Scene::new], this only works once
+pub fn take_ram() -> Option<&'static mut [u8; SPRITE_RAM]> {
+    if RAM_TAKEN.swap(true, Ordering::AcqRel) {
+        return None;
+    }
+    // SAFETY: Only handed out once, checked above
+    Some(unsafe { &mut *RAM_BUF.0.get() })
+}
+
+/// Dirty areas are merged together once there are more than this
+const MAX_DIRTY: usize = 8;
+
+const SCREEN: u8 = 240;
+
+/// Longest row of any image, in pixels
+const MAX_ROW: usize = u8::MAX as usize + 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MaskKind {
+    /// 1 bit per pixel, set bits are drawn
+    Bits,
+    /// 8-bit alpha per pixel
+    Alpha,
+}
+
+/// Where a sprite's pixels or mask are
+#[derive(Clone, Copy)]
+pub enum Source {
+    /// In the firmware or the external flash
+    Image(ImageSource),
+    /// Copied into the scene
+    Ram(Span),
+}
+
+/// Some of the scene's [SPRITE_RAM]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    start: usize,
+    len: usize,
+}
+
+impl Source {
+    /// Read `buf.len()` bytes starting `offset` bytes in
+    async fn read(
+        &self,
+        ram: &[u8],
+        spif: Option<&mut SpiFlash>,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<(), ()> {
+        match self {
+            Source::Image(source) => source.read(spif, offset, buf).await,
+            Source::Ram(span) => {
+                let src = ram[span.start..][..span.len]
+                    .get(offset..)
+                    .and_then(|d| d.get(..buf.len()))
+                    .ok_or(())?;
+                buf.copy_from_slice(src);
+                Ok(())
+            }
+        }
+    }
+
+    /// How many bytes there are, if that's known
+    fn len(&self) -> Option<usize> {
+        match self {
+            Source::Image(ImageSource::Static(data)) => Some(data.len()),
+            Source::Image(ImageSource::Flash(_)) => None,
+            Source::Ram(span) => Some(span.len),
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            Source::Ram(span) => Some(*span),
+            Source::Image(_) => None,
+        }
+    }
+
+    fn span_mut(&mut self) -> Option<&mut Span> {
+        match self {
+            Source::Ram(span) => Some(span),
+            Source::Image(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Mask {
+    pub kind: MaskKind,
+    pub source: Source,
+}
+
+impl Mask {
+    fn row_bytes(&self, width: usize) -> usize {
+        match self.kind {
+            MaskKind::Bits => (width + 7) / 8,
+            MaskKind::Alpha => width,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sprite {
+    pub width: u8,
+    pub height: u8,
+    pub pixels: Source,
+    pub mask: Option<Mask>,
+    /// Position of the top left corner, which may be off screen
+    pub x: i16,
+    pub y: i16,
+    /// Higher layers are drawn on top, ties go to the higher slot
+    pub layer: u8,
+    pub visible: bool,
+}
+
+impl Sprite {
+    /// A hidden sprite at 0, 0 on layer 0
+    pub fn new(image: Image) -> Self {
+        Self::with_pixels(image.width, image.height, Source::Image(image.source))
+    }
+
+    fn with_pixels(width: u8, height: u8, pixels: Source) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+            mask: None,
+            x: 0,
+            y: 0,
+            layer: 0,
+            visible: false,
+        }
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        if !self.visible {
+            return None;
+        }
+        Rect::clip(
+            self.x as i32,
+            self.y as i32,
+            self.width as i32,
+            self.height as i32,
+        )
+    }
+
+    /// Check that baked in or RAM data is big enough
+    fn check(&self) -> Result<(), ()> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        if width == 0 || height == 0 {
+            return Err(());
+        }
+        let short = |source: &Source, need: usize| source.len().map(|len| len < need);
+        if short(&self.pixels, width * height * 2) == Some(true) {
+            return Err(());
+        }
+        if let Some(mask) = &self.mask {
+            if short(&mask.source, mask.row_bytes(width) * height) == Some(true) {
+                return Err(());
+            }
+        }
+        Ok(())
+    }
+
+    /// The parts of the scene's memory this uses
+    fn spans(&self) -> impl Iterator<Item = Span> {
+        let mask = self.mask.and_then(|m| m.source.span());
+        self.pixels.span().into_iter().chain(mask)
+    }
+
+    fn spans_mut(&mut self) -> impl Iterator<Item = &mut Span> {
+        let mask = self.mask.as_mut().and_then(|m| m.source.span_mut());
+        self.pixels.span_mut().into_iter().chain(mask)
+    }
+}
+
+pub enum Background {
+    /// A solid RGB565 color
+    Color(u16),
+    /// A quarter image mirrored four ways around the center, see
+    /// [image::quad_row]
+    Quad(&'static Image),
+}
+
+/// An area of the screen, end exclusive
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    xs: u8,
+    xe: u8,
+    ys: u8,
+    ye: u8,
+}
+
+impl Rect {
+    const SCREEN: Self = Self {
+        xs: 0,
+        xe: SCREEN,
+        ys: 0,
+        ye: SCREEN,
+    };
+
+    /// The part of a `w` x `h` area at `x`, `y` that's on screen
+    fn clip(x: i32, y: i32, w: i32, h: i32) -> Option<Self> {
+        let xs = x.clamp(0, SCREEN as i32);
+        let xe = (x + w).clamp(0, SCREEN as i32);
+        let ys = y.clamp(0, SCREEN as i32);
+        let ye = (y + h).clamp(0, SCREEN as i32);
+        if xs >= xe || ys >= ye {
+            return None;
+        }
+        Some(Self {
+            xs: xs as u8,
+            xe: xe as u8,
+            ys: ys as u8,
+            ye: ye as u8,
+        })
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.xs < other.xe && other.xs < self.xe && self.ys < other.ye && other.ys < self.ye
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            xs: self.xs.min(other.xs),
+            xe: self.xe.max(other.xe),
+            ys: self.ys.min(other.ys),
+            ye: self.ye.max(other.ye),
+        }
+    }
+}
+
+pub struct Scene {
+    pub background: Background,
+    sprites: [Option<Sprite>; MAX_SPRITES],
+    dirty: heapless::Vec<Rect, MAX_DIRTY>,
+    /// Copied pixels and masks, packed together from the start
+    ram: &'static mut [u8; SPRITE_RAM],
+    ram_used: usize,
+}
+
+impl Scene {
+    /// `ram` comes from [take_ram]
+    pub fn new(ram: &'static mut [u8; SPRITE_RAM]) -> Self {
+        const NONE: Option<Sprite> = None;
+        let mut scene = Self {
+            background: Background::Color(0),
+            sprites: [NONE; MAX_SPRITES],
+            dirty: heapless::Vec::new(),
+            ram,
+            ram_used: 0,
+        };
+        scene.mark_all();
+        scene
+    }
+
+    /// Redraw everything on the next render
+    pub fn mark_all(&mut self) {
+        self.dirty.clear();
+        self.dirty.push(Rect::SCREEN).ok();
+    }
+
+    fn mark(&mut self, rect: Option<Rect>) {
+        let Some(mut rect) = rect else {
+            return;
+        };
+
+        // Soak up anything this touches, so no pixel is drawn twice
+        while let Some(pos) = self.dirty.iter().position(|r| r.overlaps(&rect)) {
+            rect = rect.union(&self.dirty.swap_remove(pos));
+        }
+
+        if let Err(rect) = self.dirty.push(rect) {
+            let all = self.dirty.iter().fold(rect, |acc, r| acc.union(r));
+            self.dirty.clear();
+            self.dirty.push(all).ok();
+        }
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+        self.mark_all();
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&Sprite> {
+        self.sprites.get(slot)?.as_ref()
+    }
+
+    /// Put a sprite in `slot`, replacing whatever was there
+    pub fn set(&mut self, slot: usize, sprite: Sprite) -> Result<(), ()> {
+        sprite.check()?;
+        let old = self.sprites.get_mut(slot).ok_or(())?.replace(sprite);
+        self.mark(old.and_then(|s| s.bounds()));
+        self.mark(sprite.bounds());
+        if let Some(old) = old {
+            self.release_unused(&old);
+        }
+        Ok(())
+    }
+
+    /// Put a sprite showing a copy of `pixels` in `slot`
+    pub fn set_ram(&mut self, slot: usize, width: u8, height: u8, pixels: &[u8]) -> Result<(), ()> {
+        let span = self.store(pixels)?;
+        let sprite = Sprite::with_pixels(width, height, Source::Ram(span));
+        self.set(slot, sprite).map_err(|()| self.release(span))
+    }
+
+    /// Give the sprite in `slot` a copy of `mask`
+    pub fn set_mask_ram(&mut self, slot: usize, kind: MaskKind, mask: &[u8]) -> Result<(), ()> {
+        let span = self.store(mask)?;
+        let source = Source::Ram(span);
+        self.update(slot, |s| s.mask = Some(Mask { kind, source }))
+            .map_err(|()| self.release(span))
+    }
+
+    pub fn remove(&mut self, slot: usize) -> Option<Sprite> {
+        let old = self.sprites.get_mut(slot)?.take();
+        self.mark(old.as_ref().and_then(|s| s.bounds()));
+        if let Some(old) = &old {
+            self.release_unused(old);
+        }
+        old
+    }
+
+    /// Copy `data` in after everything else
+    fn store(&mut self, data: &[u8]) -> Result<Span, ()> {
+        let start = self.ram_used;
+        let dst = self.ram.get_mut(start..start + data.len()).ok_or(())?;
+        dst.copy_from_slice(data);
+        self.ram_used += data.len();
+        Ok(Span {
+            start,
+            len: data.len(),
+        })
+    }
+
+    /// Give back the memory `old` used that no sprite uses any more
+    fn release_unused(&mut self, old: &Sprite) {
+        let mut spans = heapless::Vec::<Span, 2>::new();
+        for span in old.spans() {
+            let used = self
+                .sprites
+                .iter()
+                .flatten()
+                .any(|s| s.spans().any(|s| s == span));
+            if !used {
+                spans.push(span).ok();
+            }
+        }
+        // Highest first, so releasing one doesn't move the other
+        spans.sort_unstable_by_key(|span| Reverse(span.start));
+        for span in spans {
+            self.release(span);
+        }
+    }
+
+    /// Close the gap left by `span`, moving anything after it down
+    fn release(&mut self, span: Span) {
+        let end = span.start + span.len;
+        self.ram.copy_within(end..self.ram_used, span.start);
+        self.ram_used -= span.len;
+        for sprite in self.sprites.iter_mut().flatten() {
+            for s in sprite.spans_mut() {
+                if s.start >= end {
+                    s.start -= span.len;
+                }
+            }
+        }
+    }
+
+    /// Change a sprite, marking where it was and where it ends up
+    pub fn update(&mut self, slot: usize, f: impl FnOnce(&mut Sprite)) -> Result<(), ()> {
+        let mut sprite = *self.get(slot).ok_or(())?;
+        f(&mut sprite);
+        self.set(slot, sprite)
+    }
+
+    /// Redraw everything marked dirty since the last render
+    pub async fn render(
+        &mut self,
+        lcd: &mut LcdPins,
+        mut spif: Option<&mut SpiFlash>,
+    ) -> Result<(), ()> {
+        while let Some(rect) = self.dirty.pop() {
+            if let Err(()) = self.render_rect(lcd, spif.as_deref_mut(), rect).await {
+                // Try again next time
+                self.mark(Some(rect));
+                return Err(());
+            }
+        }
+        Ok(())
+    }
+
+    async fn render_rect(
+        &self,
+        lcd: &mut LcdPins,
+        mut spif: Option<&mut SpiFlash>,
+        rect: Rect,
+    ) -> Result<(), ()> {
+        // Visible sprites touching this area, bottom layer first
+        let mut order = heapless::Vec::<(u8, usize), MAX_SPRITES>::new();
+        for (slot, sprite) in self.sprites.iter().enumerate() {
+            if let Some(sprite) = sprite {
+                if sprite.bounds().map(|b| b.overlaps(&rect)).unwrap_or(false) {
+                    order.push((sprite.layer, slot)).ok();
+                }
+            }
+        }
+        order.sort_unstable();
+
+        let mut line = [0u16; SCREEN as usize];
+        let mut bytes = [0u8; SCREEN as usize * 2];
+        let mut scratch = [0u8; MAX_ROW * 2];
+        let mut mask_buf = [0u8; MAX_ROW];
+
+        let xs = rect.xs as usize;
+        let xe = rect.xe as usize;
+
+        lcd.start_write(rect.xs, rect.xe, rect.ys, rect.ye)
+            .await
+            .map_err(drop)?;
+
+        let mut res = Ok(());
+        for y in rect.ys..rect.ye {
+            let line = &mut line[xs..xe];
+            res = self
+                .background_row(spif.as_deref_mut(), y as usize, xs, line, &mut scratch)
+                .await;
+
+            for (_, slot) in order.iter() {
+                if res.is_err() {
+                    break;
+                }
+                if let Some(sprite) = &self.sprites[*slot] {
+                    res = sprite_row(
+                        &self.ram[..],
+                        spif.as_deref_mut(),
+                        sprite,
+                        y as i32,
+                        xs,
+                        line,
+                        &mut scratch,
+                        &mut mask_buf,
+                    )
+                    .await;
+                }
+            }
+            if res.is_err() {
+                break;
+            }
+
+            let bytes = &mut bytes[..line.len() * 2];
+            for (px, dst) in line.iter().zip(bytes.chunks_exact_mut(2)) {
+                dst.copy_from_slice(&px.to_be_bytes());
+            }
+            if lcd.write_pixels(bytes).await.is_err() {
+                res = Err(());
+                break;
+            }
+        }
+
+        lcd.end_write();
+        res
+    }
+
+    /// Fill `line` with the background for row `y`, starting at `x`
+    async fn background_row(
+        &self,
+        spif: Option<&mut SpiFlash>,
+        y: usize,
+        x: usize,
+        line: &mut [u16],
+        scratch: &mut [u8],
+    ) -> Result<(), ()> {
+        let img = match self.background {
+            Background::Color(color) => {
+                line.fill(color);
+                return Ok(());
+            }
+            Background::Quad(img) => img,
+        };
+
+        image::quad_row(spif, img, y, x, line, scratch).await
+    }
+}
+
+/// Draw the part of `sprite` on row `y` over `line`, which starts at `x`
+async fn sprite_row(
+    ram: &[u8],
+    mut spif: Option<&mut SpiFlash>,
+    sprite: &Sprite,
+    y: i32,
+    x: usize,
+    line: &mut [u16],
+    scratch: &mut [u8],
+    mask_buf: &mut [u8],
+) -> Result<(), ()> {
+    let width = sprite.width as usize;
+    let sy = y - sprite.y as i32;
+    if sy < 0 || sy >= sprite.height as i32 {
+        return Ok(());
+    }
+    let sy = sy as usize;
+
+    // The columns of `line` the sprite covers
+    let x0 = (sprite.x as i32).max(x as i32);
+    let x1 = (sprite.x as i32 + width as i32).min((x + line.len()) as i32);
+    if x0 >= x1 {
+        return Ok(());
+    }
+    let sx = (x0 - sprite.x as i32) as usize;
+    let len = (x1 - x0) as usize;
+    let line = &mut line[(x0 as usize - x)..][..len];
+
+    let pixels = &mut scratch[..len * 2];
+    sprite
+        .pixels
+        .read(ram, spif.as_deref_mut(), (sy * width + sx) * 2, pixels)
+        .await?;
+
+    // Read the mask for the whole row, it's small
+    let mask = match &sprite.mask {
+        Some(mask) => {
+            let row_bytes = mask.row_bytes(width);
+            let buf = &mut mask_buf[..row_bytes];
+            mask.source.read(ram, spif, sy * row_bytes, buf).await?;
+            Some((mask.kind, &*buf))
+        }
+        None => None,
+    };
+
+    for (i, (dst, src)) in line.iter_mut().zip(pixels.chunks_exact(2)).enumerate() {
+        let col = sx + i;
+        let alpha = match mask {
+            None => 255,
+            Some((MaskKind::Bits, bits)) => {
+                if bits[col / 8] & (0x80 >> (col % 8)) != 0 {
+                    255
+                } else {
+                    0
+                }
+            }
+            Some((MaskKind::Alpha, alphas)) => alphas[col],
+        };
+
+        let px = u16::from_be_bytes([src[0], src[1]]);
+        *dst = match alpha {
+            0 => *dst,
+            255 => px,
+            a => blend(rgb565_to_rgb8(px), rgb565_to_rgb8(*dst), a),
+        };
+    }
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Drawing strings with any font from [crate::fonts::FONTS]
+
+use smart_leds::RGB8;
+
+use crate::{
+    fonts::{Font, CHAR_BUF_SIZE},
+    lcd::LcdPins,
+};
+
+/// Decode `txt` as UTF-8. Any byte that isn't part of a valid sequence is
+/// taken as Latin-1 instead, so plain Latin-1 strings show up as expected.
+pub fn chars(txt: &[u8]) -> impl Iterator<Item = char> + '_ {
+    let mut rest = txt;
+    core::iter::from_fn(move || {
+        let (&first, tail) = rest.split_first()?;
+        let len = match first {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => 0,
+        };
+
+        let decoded = rest
+            .get(..len)
+            .filter(|seq| !seq.is_empty())
+            .and_then(|seq| core::str::from_utf8(seq).ok())
+            .and_then(|s| s.chars().next());
+
+        match decoded {
+            Some(ch) => {
+                rest = &rest[len..];
+                Some(ch)
+            }
+            None => {
+                rest = tail;
+                Some(char::from(first))
+            }
+        }
+    })
+}
+
+/// Width in pixels of `txt` when drawn with `font`
+pub fn text_width(font: &Font, txt: &[u8]) -> usize {
+    chars(txt).map(|ch| font.advance(ch)).sum()
+}
+
+/// Draw `txt` with its top left corner at `x`, `y`.
+///
+/// Characters that would run off the right edge of the screen are dropped.
+pub async fn draw_str(
+    lcd: &mut LcdPins,
+    font: &Font,
+    x: u8,
+    y: u8,
+    txt: &[u8],
+    fg: RGB8,
+    bg: RGB8,
+) -> Result<(), ()> {
+    let mut buf = [0u8; CHAR_BUF_SIZE];
+
+    let ch_h = font.char_height_px;
+    if y as usize + ch_h > 240 {
+        return Err(());
+    }
+
+    let mut x_pos = x as usize;
+    for ch in chars(txt) {
+        let ch_w = font.advance(ch);
+        if x_pos + ch_w > 240 {
+            break;
+        }
+        if ch_w == 0 {
+            continue;
+        }
+
+        font.render(&mut buf, ch, fg, bg)?;
+        let buf = &buf[..ch_w * ch_h * 2];
+
+        lcd.draw(x_pos as u8, (x_pos + ch_w) as u8, y, y + ch_h as u8, buf)
+            .await
+            .map_err(drop)?;
+
+        x_pos += ch_w;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,656 @@
+//! Widgets for the round display
+//!
+//! Everything here is laid out around the center of the panel, and drawn in
+//! the colors of a [Theme]. Shapes are painted one row at a time, only
+//! covering the part of the row inside the circle they belong to, so a
+//! widget can be redrawn without wiping out whatever is around it.
+//!
+//! Interactive widgets take an [Input] from [next_input] and say what
+//! happened with a [Response]. The buttons are used as:
+//!
+//! * A: previous
+//! * B: next
+//! * C: select
+//! * D: back
+//!
+//! and, where it fits, the dial picks directly.
+
+use core::fmt::Write;
+
+use embassy_futures::select::{select, Either};
+
+use crate::{
+    buttons::{Button, ButtonEvent, BUTTON_EVENTS},
+    color::rgb565_to_rgb8,
+    dial::{DIAL, DIAL_MAX},
+    fmath::{isqrt, SINE_LUT},
+    fonts::Font,
+    lcd::LcdPins,
+    text::{draw_str, text_width},
+};
+
+const SIZE: i32 = 240;
+const CENTER: i32 = SIZE / 2;
+
+/// Most items a [Menu] can spread around the rim
+pub const MENU_MAX: usize = 8;
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    /// Text
+    pub fg: u16,
+    /// Behind everything
+    pub bg: u16,
+    /// Selections, and the filled part of gauges
+    pub accent: u16,
+    /// The empty part of gauges, and unselected items
+    pub track: u16,
+}
+
+impl Theme {
+    pub const fn new() -> Self {
+        Self {
+            fg: 0xFFFF,
+            bg: 0x0000,
+            accent: 0xFD20,
+            track: 0x4208,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Input {
+    Prev,
+    Next,
+    Select,
+    Back,
+    /// The dial moved, `0..=DIAL_MAX`
+    Dial(u16),
+}
+
+/// Throw away anything that happened before now, so a widget doesn't act on
+/// presses from before it was shown
+pub fn flush_input() {
+    while BUTTON_EVENTS.try_receive().is_ok() {}
+    DIAL.reset();
+}
+
+/// Wait for the next button press or dial movement. Holding A or B repeats.
+pub async fn next_input() -> Input {
+    loop {
+        let event = match select(BUTTON_EVENTS.receive(), DIAL.wait()).await {
+            Either::First(event) => event,
+            Either::Second(level) => return Input::Dial(level),
+        };
+        match event {
+            ButtonEvent::Pressed(Button::A) | ButtonEvent::Repeat(Button::A) => return Input::Prev,
+            ButtonEvent::Pressed(Button::B) | ButtonEvent::Repeat(Button::B) => return Input::Next,
+            ButtonEvent::Pressed(Button::C) => return Input::Select,
+            ButtonEvent::Pressed(Button::D) => return Input::Back,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Response {
+    /// Nothing changed
+    Ignored,
+    /// Needs redrawing
+    Changed,
+    /// Finished, with the chosen item or value
+    Done(i32),
+    /// Backed out of
+    Cancelled,
+}
+
+/// Angle of a point from the center, in 1/256ths of a turn clockwise from
+/// 12 o'clock, the same units as [SINE_LUT]. `dy` grows downwards.
+pub fn angle_of(dx: i32, dy: i32) -> u8 {
+    let (ax, ay) = (dx.unsigned_abs(), dy.unsigned_abs());
+    if ax == 0 && ay == 0 {
+        return 0;
+    }
+
+    // atan(t) for t in 0..=1 is close to 32t + 11.1t(1 - t) in these units
+    let octant = |n: u32, d: u32| {
+        let t = n * 256 / d;
+        (t * 32 + t * (256 - t) * 11 / 256 + 128) / 256
+    };
+
+    // Angle from the vertical, within the quarter
+    let a = if ax <= ay {
+        octant(ax, ay)
+    } else {
+        64 - octant(ay, ax)
+    } as u8;
+
+    match (dx >= 0, dy <= 0) {
+        (true, true) => a,
+        (true, false) => 128 - a,
+        (false, false) => 128 + a,
+        (false, true) => 0u8.wrapping_sub(a),
+    }
+}
+
+/// The point `r` pixels from the center at `angle`
+pub fn point_at(angle: u8, r: i32) -> (i32, i32) {
+    let sin = SINE_LUT[angle as usize] as i32;
+    let cos = SINE_LUT[angle.wrapping_add(64) as usize] as i32;
+    (CENTER + r * sin / 32767, CENTER - r * cos / 32767)
+}
+
+/// Position of the middle of pixel `x` or `y` from the center, in half pixels
+fn half_px(v: i32) -> i32 {
+    2 * v + 1 - SIZE
+}
+
+/// The columns `xs..xe` of row `y` whose middles are less than `r` from the
+/// center
+fn chord(y: i32, r: i32) -> Option<(i32, i32)> {
+    let dy = half_px(y);
+    let m = 4 * r * r - dy * dy;
+    if m <= 0 {
+        return None;
+    }
+    // Columns with |half_px(x)| <= s
+    let s = isqrt((m - 1) as u32) as i32;
+    let xs = (SIZE - s).div_euclid(2).max(0);
+    let xe = ((SIZE - 1 + s).div_euclid(2) + 1).min(SIZE);
+    (xs < xe).then_some((xs, xe))
+}
+
+/// Part of a ring, `sweep` long from `start`. A sweep of 256 is a full turn.
+#[derive(Clone, Copy)]
+struct Arc {
+    start: u8,
+    sweep: u16,
+}
+
+impl Arc {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        if self.sweep >= 256 {
+            return true;
+        }
+        let angle = angle_of(half_px(x), half_px(y));
+        (angle.wrapping_sub(self.start) as u16) < self.sweep
+    }
+}
+
+/// Paint every pixel of rows `ys..ye` between `inner` and `outer` pixels
+/// from the center with the color from `f`
+async fn paint_ring(
+    lcd: &mut LcdPins,
+    inner: i32,
+    outer: i32,
+    ys: i32,
+    ye: i32,
+    f: impl Fn(i32, i32) -> u16,
+) -> Result<(), ()> {
+    let mut buf = [0u8; SIZE as usize * 2];
+    let ys = ys.max(CENTER - outer).max(0);
+    let ye = ye.min(CENTER + outer).min(SIZE);
+
+    for y in ys..ye {
+        let Some((os, oe)) = chord(y, outer) else {
+            continue;
+        };
+        let spans = match chord(y, inner) {
+            Some((is, ie)) => [(os, is), (ie, oe)],
+            None => [(os, oe), (0, 0)],
+        };
+
+        for (xs, xe) in spans {
+            if xs >= xe {
+                continue;
+            }
+            let row = &mut buf[..(xe - xs) as usize * 2];
+            for (x, px) in (xs..xe).zip(row.chunks_exact_mut(2)) {
+                px.copy_from_slice(&f(x, y).to_be_bytes());
+            }
+            lcd.start_write(xs as u8, xe as u8, y as u8, y as u8 + 1)
+                .await
+                .map_err(drop)?;
+            let res = lcd.write_pixels(row).await.map_err(drop);
+            lcd.end_write();
+            res?;
+        }
+    }
+    Ok(())
+}
+
+/// Draw `txt` centered on `cx`, `cy`
+async fn label(
+    lcd: &mut LcdPins,
+    font: &Font,
+    cx: i32,
+    cy: i32,
+    txt: &[u8],
+    fg: u16,
+    bg: u16,
+) -> Result<(), ()> {
+    let w = text_width(font, txt) as i32;
+    let h = font.char_height_px as i32;
+    let x = (cx - w / 2).clamp(0, SIZE - 1);
+    let y = (cy - h / 2).clamp(0, SIZE - h);
+    draw_str(
+        lcd,
+        font,
+        x as u8,
+        y as u8,
+        txt,
+        rgb565_to_rgb8(fg),
+        rgb565_to_rgb8(bg),
+    )
+    .await
+}
+
+/// Fill the whole circle with the background
+pub async fn clear(lcd: &mut LcdPins, theme: &Theme) -> Result<(), ()> {
+    paint_ring(lcd, 0, CENTER, 0, SIZE, |_, _| theme.bg).await
+}
+
+/// A number in the middle of the screen, with a caption under it
+pub struct Readout<'a> {
+    pub value: i32,
+    pub caption: &'a [u8],
+}
+
+impl Readout<'_> {
+    /// Everything drawn stays inside this radius
+    const RADIUS: i32 = 92;
+
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        let h = font.char_height_px as i32;
+        let caption_y = CENTER + h + 2;
+
+        let bottom = if self.caption.is_empty() {
+            CENTER + h / 2 + 1
+        } else {
+            caption_y + h / 2 + 1
+        };
+        paint_ring(lcd, 0, Self::RADIUS, CENTER - h / 2, bottom, |_, _| {
+            theme.bg
+        })
+        .await?;
+
+        let mut num = heapless::String::<12>::new();
+        write!(&mut num, "{}", self.value).ok();
+        label(
+            lcd,
+            font,
+            CENTER,
+            CENTER,
+            num.as_bytes(),
+            theme.fg,
+            theme.bg,
+        )
+        .await?;
+
+        if !self.caption.is_empty() {
+            label(
+                lcd,
+                font,
+                CENTER,
+                caption_y,
+                self.caption,
+                theme.track,
+                theme.bg,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// An arc around the rim filled in proportion to a value, with the value in
+/// the middle. It can be adjusted with the buttons or the dial.
+pub struct Gauge<'a> {
+    pub value: i32,
+    pub min: i32,
+    pub max: i32,
+    /// How far one press of previous or next moves the value
+    pub step: i32,
+    pub caption: &'a [u8],
+}
+
+impl Gauge<'_> {
+    /// From 7:30 clockwise round to 4:30
+    const START: u8 = 160;
+    const SWEEP: u16 = 192;
+    const INNER: i32 = 96;
+    const OUTER: i32 = 116;
+
+    pub fn new(value: i32, min: i32, max: i32) -> Self {
+        let (min, max) = (min.min(max), min.max(max));
+        Self {
+            value: value.clamp(min, max),
+            min,
+            max,
+            step: 1,
+            caption: b"",
+        }
+    }
+
+    fn filled(&self) -> u16 {
+        let span = (self.max - self.min).max(1) as i64;
+        let along = (self.value.clamp(self.min, self.max) - self.min) as i64;
+        (along * Self::SWEEP as i64 / span) as u16
+    }
+
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        paint_ring(lcd, 0, Self::INNER, 0, SIZE, |_, _| theme.bg).await?;
+        self.redraw(lcd, font, theme).await
+    }
+
+    /// Draw just the arc and value
+    pub async fn redraw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        let track = Arc {
+            start: Self::START,
+            sweep: Self::SWEEP,
+        };
+        let fill = Arc {
+            sweep: self.filled(),
+            ..track
+        };
+        paint_ring(lcd, Self::INNER, Self::OUTER, 0, SIZE, |x, y| {
+            if fill.sweep > 0 && fill.contains(x, y) {
+                theme.accent
+            } else if track.contains(x, y) {
+                theme.track
+            } else {
+                theme.bg
+            }
+        })
+        .await?;
+
+        Readout {
+            value: self.value,
+            caption: self.caption,
+        }
+        .draw(lcd, font, theme)
+        .await
+    }
+
+    pub fn handle(&mut self, input: Input) -> Response {
+        let value = match input {
+            Input::Prev => self.value.saturating_sub(self.step),
+            Input::Next => self.value.saturating_add(self.step),
+            Input::Dial(level) => {
+                let span = (self.max - self.min) as i64;
+                self.min + (level as i64 * span / DIAL_MAX as i64) as i32
+            }
+            Input::Select => return Response::Done(self.value),
+            Input::Back => return Response::Cancelled,
+        };
+        let value = value.clamp(self.min, self.max);
+        if value == self.value {
+            return Response::Ignored;
+        }
+        self.value = value;
+        Response::Changed
+    }
+}
+
+/// A ring around the rim filling up clockwise from 12 o'clock, with the
+/// percentage in the middle
+pub struct Progress {
+    /// 0..=100
+    pub percent: u8,
+}
+
+impl Progress {
+    const INNER: i32 = 100;
+    const OUTER: i32 = 116;
+
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        let percent = self.percent.min(100);
+        let fill = Arc {
+            start: 0,
+            sweep: percent as u16 * 256 / 100,
+        };
+        paint_ring(lcd, Self::INNER, Self::OUTER, 0, SIZE, |x, y| {
+            if fill.sweep > 0 && fill.contains(x, y) {
+                theme.accent
+            } else {
+                theme.track
+            }
+        })
+        .await?;
+
+        Readout {
+            value: percent as i32,
+            caption: b"%",
+        }
+        .draw(lcd, font, theme)
+        .await
+    }
+}
+
+/// Up to [MENU_MAX] short items spread around the rim, starting at 12
+/// o'clock. The dial points at an item directly.
+pub struct Menu<'a> {
+    pub items: &'a [&'a [u8]],
+    pub selected: usize,
+}
+
+impl<'a> Menu<'a> {
+    const INNER: i32 = 104;
+    const OUTER: i32 = 118;
+    /// How far out the labels are centered
+    const LABEL: i32 = 72;
+
+    pub fn new(items: &'a [&'a [u8]]) -> Self {
+        Self { items, selected: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.items.len().min(MENU_MAX)
+    }
+
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        paint_ring(lcd, 0, Self::INNER, 0, SIZE, |_, _| theme.bg).await?;
+        self.redraw(lcd, font, theme).await
+    }
+
+    /// Draw the rim and labels, which is all that changes with the selection
+    pub async fn redraw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        let n = self.len() as u32;
+        if n == 0 {
+            return Ok(());
+        }
+
+        // Each item's segment is centered on its label, with a gap either side
+        let half = (128 / n) as u8;
+        paint_ring(lcd, Self::INNER, Self::OUTER, 0, SIZE, |x, y| {
+            let angle = angle_of(half_px(x), half_px(y)).wrapping_add(half) as u32;
+            let idx = (angle * n / 256) as usize;
+            let within = angle * n % 256;
+            if !(12..244).contains(&within) {
+                theme.bg
+            } else if idx == self.selected {
+                theme.accent
+            } else {
+                theme.track
+            }
+        })
+        .await?;
+
+        for (idx, item) in self.items[..self.len()].iter().enumerate() {
+            let angle = (idx as u32 * 256 / n) as u8;
+            let (x, y) = point_at(angle, Self::LABEL);
+            let (fg, bg) = if idx == self.selected {
+                (theme.bg, theme.accent)
+            } else {
+                (theme.fg, theme.bg)
+            };
+            label(lcd, font, x, y, item, fg, bg).await?;
+        }
+        Ok(())
+    }
+
+    pub fn handle(&mut self, input: Input) -> Response {
+        let n = self.len();
+        if n == 0 {
+            return Response::Cancelled;
+        }
+        let selected = match input {
+            Input::Prev => (self.selected + n - 1) % n,
+            Input::Next => (self.selected + 1) % n,
+            Input::Dial(level) => level as usize * n / (DIAL_MAX as usize + 1),
+            Input::Select => return Response::Done(self.selected as i32),
+            Input::Back => return Response::Cancelled,
+        };
+        if selected == self.selected {
+            return Response::Ignored;
+        }
+        self.selected = selected;
+        Response::Changed
+    }
+}
+
+/// A vertical list that scrolls to keep the selected item in the middle,
+/// where the screen is widest
+pub struct List<'a> {
+    pub items: &'a [&'a [u8]],
+    pub selected: usize,
+}
+
+impl<'a> List<'a> {
+    /// Space used by the list, top to bottom
+    const HEIGHT: i32 = 200;
+    /// The selection bar stays this far inside the edge
+    const BAR: i32 = 112;
+
+    pub fn new(items: &'a [&'a [u8]]) -> Self {
+        Self { items, selected: 0 }
+    }
+
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        let pitch = font.char_height_px as i32 + 4;
+        let rows = (Self::HEIGHT / pitch).max(1) as usize;
+        let top = self
+            .selected
+            .saturating_sub(rows / 2)
+            .min(self.items.len().saturating_sub(rows));
+        let y0 = CENTER - (rows as i32 * pitch) / 2;
+
+        let bar_ys = y0 + (self.selected - top) as i32 * pitch;
+        let bar_ye = bar_ys + pitch;
+        paint_ring(lcd, 0, CENTER, 0, SIZE, |x, y| {
+            let (dx, dy) = (half_px(x), half_px(y));
+            let in_bar =
+                (bar_ys..bar_ye).contains(&y) && dx * dx + dy * dy < 4 * Self::BAR * Self::BAR;
+            if in_bar {
+                theme.accent
+            } else {
+                theme.bg
+            }
+        })
+        .await?;
+
+        for (row, item) in self.items.iter().enumerate().skip(top).take(rows) {
+            let cy = y0 + (row - top) as i32 * pitch + pitch / 2;
+            let (fg, bg) = if row == self.selected {
+                (theme.bg, theme.accent)
+            } else {
+                (theme.fg, theme.bg)
+            };
+            label(lcd, font, CENTER, cy, item, fg, bg).await?;
+        }
+        Ok(())
+    }
+
+    pub fn handle(&mut self, input: Input) -> Response {
+        let n = self.items.len();
+        if n == 0 {
+            return Response::Cancelled;
+        }
+        let selected = match input {
+            Input::Prev => self.selected.saturating_sub(1),
+            Input::Next => (self.selected + 1).min(n - 1),
+            Input::Dial(level) => level as usize * n / (DIAL_MAX as usize + 1),
+            Input::Select => return Response::Done(self.selected as i32),
+            Input::Back => return Response::Cancelled,
+        };
+        if selected == self.selected {
+            return Response::Ignored;
+        }
+        self.selected = selected;
+        Response::Changed
+    }
+}
+
+/// Lines of text over the whole screen, until select or back is pressed
+pub struct Alert<'a> {
+    pub lines: &'a [&'a [u8]],
+}
+
+impl Alert<'_> {
+    const RIM: i32 = 110;
+
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        paint_ring(lcd, 0, CENTER, 0, SIZE, |x, y| {
+            let (dx, dy) = (half_px(x), half_px(y));
+            if dx * dx + dy * dy < 4 * Self::RIM * Self::RIM {
+                theme.bg
+            } else {
+                theme.accent
+            }
+        })
+        .await?;
+
+        let pitch = font.char_height_px as i32 + 2;
+        let y0 = CENTER - (self.lines.len() as i32 * pitch) / 2 + pitch / 2;
+        for (idx, line) in self.lines.iter().enumerate() {
+            let cy = y0 + idx as i32 * pitch;
+            label(lcd, font, CENTER, cy, line, theme.fg, theme.bg).await?;
+        }
+        Ok(())
+    }
+
+    pub fn handle(&mut self, input: Input) -> Response {
+        match input {
+            Input::Select => Response::Done(0),
+            Input::Back => Response::Cancelled,
+            _ => Response::Ignored,
+        }
+    }
+}
+
+/// Any of the interactive widgets, for running one until it's done
+pub enum Modal<'a> {
+    Menu(Menu<'a>),
+    List(List<'a>),
+    Gauge(Gauge<'a>),
+    Alert(Alert<'a>),
+}
+
+impl Modal<'_> {
+    pub async fn draw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        match self {
+            Modal::Menu(w) => w.draw(lcd, font, theme).await,
+            Modal::List(w) => w.draw(lcd, font, theme).await,
+            Modal::Gauge(w) => w.draw(lcd, font, theme).await,
+            Modal::Alert(w) => w.draw(lcd, font, theme).await,
+        }
+    }
+
+    /// Draw whatever can change after a [Response::Changed]
+    pub async fn redraw(&self, lcd: &mut LcdPins, font: &Font, theme: &Theme) -> Result<(), ()> {
+        match self {
+            Modal::Menu(w) => w.redraw(lcd, font, theme).await,
+            Modal::Gauge(w) => w.redraw(lcd, font, theme).await,
+            _ => self.draw(lcd, font, theme).await,
+        }
+    }
+
+    pub fn handle(&mut self, input: Input) -> Response {
+        match self {
+            Modal::Menu(w) => w.handle(input),
+            Modal::List(w) => w.handle(input),
+            Modal::Gauge(w) => w.handle(input),
+            Modal::Alert(w) => w.handle(input),
+        }
+    }
+}
@@ -2,15 +2,86 @@ use embassy_rp::{bind_interrupts, pio::{InterruptHandler, Instance, StateMachine
 use fixed::types::U24F8;
 use smart_leds::RGB8;
 use fixed_macro::fixed;
+use embassy_time::{Duration, Timer};
 
 bind_interrupts!(pub struct Irqs {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
 });
 
+/// The order a part wants its color channels sent in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+/// What a strip's pixels look like on the wire
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub order: ColorOrder,
+    /// A fourth, white, channel after the colors, like the SK6812 RGBW
+    pub white: bool,
+}
+
+impl Format {
+    /// The format a part's datasheet gives as the order of its channels,
+    /// like "GRB" for the WS2812 and most of its clones, or "GRBW" for the
+    /// SK6812 RGBW. Anything else fails the build when used in a const.
+    pub const fn new(name: &str) -> Self {
+        let (order, white) = match name.as_bytes() {
+            [c0, c1, c2] => ((*c0, *c1, *c2), false),
+            [c0, c1, c2, b'W'] => ((*c0, *c1, *c2), true),
+            _ => panic!("pixel formats look like GRB or GRBW"),
+        };
+        let order = match order {
+            (b'R', b'G', b'B') => ColorOrder::Rgb,
+            (b'R', b'B', b'G') => ColorOrder::Rbg,
+            (b'G', b'R', b'B') => ColorOrder::Grb,
+            (b'G', b'B', b'R') => ColorOrder::Gbr,
+            (b'B', b'R', b'G') => ColorOrder::Brg,
+            (b'B', b'G', b'R') => ColorOrder::Bgr,
+            _ => panic!("pixel formats look like GRB or GRBW"),
+        };
+        Self { order, white }
+    }
+
+    fn bits(&self) -> u8 {
+        if self.white {
+            32
+        } else {
+            24
+        }
+    }
+
+    /// A pixel packed into the top of a word, first bit out at the top.
+    /// The white channel takes whatever all three colors share.
+    fn pack(&self, rgb: RGB8) -> u32 {
+        let (r, g, b) = (rgb.r as u32, rgb.g as u32, rgb.b as u32);
+        let w = if self.white { r.min(g).min(b) } else { 0 };
+        let (r, g, b) = (r - w, g - w, b - w);
+        let (c0, c1, c2) = match self.order {
+            ColorOrder::Rgb => (r, g, b),
+            ColorOrder::Rbg => (r, b, g),
+            ColorOrder::Grb => (g, r, b),
+            ColorOrder::Gbr => (g, b, r),
+            ColorOrder::Brg => (b, r, g),
+            ColorOrder::Bgr => (b, g, r),
+        };
+        (c0 << 24) | (c1 << 16) | (c2 << 8) | w
+    }
+}
+
+/// Up to `N` pixels on state machine `S`
 pub struct Ws2812<'d, P: Instance, const S: usize, const N: usize> {
     dma: PeripheralRef<'d, AnyChannel>,
     sm: StateMachine<'d, P, S>,
-    _pow: Output<'static, AnyPin>,
+    format: Format,
+    /// Switches the strip's supply, only on while something is lit
+    pow: Option<Output<'static, AnyPin>>,
 }
 
 impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
@@ -19,7 +90,8 @@ impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
         mut sm: StateMachine<'d, P, S>,
         dma: impl Peripheral<P = impl Channel> + 'd,
         pin: impl PioPin,
-        mut pow: Output<'static, AnyPin>,
+        mut pow: Option<Output<'static, AnyPin>>,
+        format: Format,
     ) -> Self {
         into_ref!(dma);
 
@@ -71,49 +143,54 @@ impl<'d, P: Instance, const S: usize, const N: usize> Ws2812<'d, P, S, N> {
         cfg.fifo_join = FifoJoin::TxOnly;
         cfg.shift_out = ShiftConfig {
             auto_fill: true,
-            threshold: 24,
+            threshold: format.bits(),
             direction: ShiftDirection::Left,
         };
 
         sm.set_config(&cfg);
         sm.set_enable(true);
 
-        // todo, for now just turn on the LED immediately
-        pow.set_high();
+        // Off until there's something to show
+        if let Some(pow) = pow.as_mut() {
+            pow.set_low();
+        }
 
         Self {
             dma: dma.map_into(),
             sm,
-            _pow: pow,
+            format,
+            pow,
         }
     }
 
-    pub async fn write(&mut self, colors: &[RGB8; N]) {
+    /// Show the first `N` of `colors`, powering the strip down when they're
+    /// all black
+    pub async fn write(&mut self, colors: &[RGB8]) {
+        let colors = &colors[..colors.len().min(N)];
+        if let Some(pow) = self.pow.as_mut() {
+            if colors.iter().all(|c| *c == RGB8::default()) {
+                pow.set_low();
+                return;
+            }
+            if pow.is_set_low() {
+                pow.set_high();
+                // Give the pixels a moment to come up before talking to them
+                Timer::after(Duration::from_millis(1)).await;
+            }
+        }
+
         // Precompute the word bytes from the colors
         let mut words = [0u32; N];
-        for i in 0..N {
-            let word = (u32::from(colors[i].g) << 24) | (u32::from(colors[i].r) << 16) | (u32::from(colors[i].b) << 8);
-            words[i] = word;
+        for (word, color) in words.iter_mut().zip(colors.iter()) {
+            *word = self.format.pack(*color);
         }
 
         // DMA transfer
-        self.sm.tx().dma_push(self.dma.reborrow(), &words).await;
-    }
-}
-
-/// Input a value 0 to 255 to get a color value
-/// The colours are a transition r - g - b - back to r.
-pub fn wheel(mut wheel_pos: u8) -> RGB8 {
-    wheel_pos = 255 - wheel_pos;
-    if wheel_pos < 85 {
-        return (255 - wheel_pos * 3, 0, wheel_pos * 3).into();
-    }
-    if wheel_pos < 170 {
-        wheel_pos -= 85;
-        return (0, wheel_pos * 3, 255 - wheel_pos * 3).into();
+        self.sm
+            .tx()
+            .dma_push(self.dma.reborrow(), &words[..colors.len()])
+            .await;
     }
-    wheel_pos -= 170;
-    (wheel_pos * 3, 255 - wheel_pos * 3, 0).into()
 }
 
 pub fn gamma_one(rgb: RGB8) -> RGB8 {
@@ -0,0 +1,48 @@
+#!/usr/bin/env python3
+"""Save a screenshot from the badge.
+
+Sends `screenshot` over the USB serial port, and writes the PPM that comes
+back to a file.
+
+    python3 tools/screenshot.py /dev/ttyACM0 shot.ppm
+
+Needs pyserial.
+"""
+
+import sys
+
+import serial
+
+
+def main():
+    if len(sys.argv) != 3:
+        sys.exit(f"usage: {sys.argv[0]} PORT OUT.ppm")
+    port, out = sys.argv[1:]
+
+    with serial.Serial(port, timeout=10) as ser:
+        ser.reset_input_buffer()
+        ser.write(b"screenshot\r")
+
+        # Skip the echo until the start of the frame
+        while True:
+            line = ser.readline()
+            if not line:
+                sys.exit("timed out waiting for the screenshot")
+            if line.startswith(b"SCREENSHOT "):
+                length = int(line.split()[1])
+                break
+
+        data = ser.read(length)
+        if len(data) != length:
+            sys.exit(f"expected {length} bytes, got {len(data)}")
+
+        trailer = ser.read(len(b"\r\nEND SCREENSHOT\r\n"))
+        if b"END SCREENSHOT" not in trailer:
+            sys.exit("missing the end of the frame")
+
+    with open(out, "wb") as f:
+        f.write(data)
+
+
+if __name__ == "__main__":
+    main()